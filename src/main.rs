@@ -17,12 +17,13 @@ along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
 */
 
 use rocket::{
+    data::{Limits, ToByteUnit},
     http::Status,
     request::{FromRequest, Outcome, Request},
-    response::{stream::Event, Responder, Result},
-    tokio::sync::{mpsc, Mutex},
+    response::{Responder, Result},
+    tokio::sync::Mutex,
 };
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 use tokio_postgres::Client;
 
 #[macro_use]
@@ -32,7 +33,8 @@ extern crate dotenv;
 mod routes;
 mod utils;
 
-pub type SSEClients = Arc<Mutex<Vec<(String, mpsc::UnboundedSender<Event>)>>>;
+pub type SSEClients = Arc<Mutex<Vec<utils::sse::SSEClient>>>;
+pub type Database = Arc<Client>;
 
 pub struct Auth(String);
 #[rocket::async_trait]
@@ -44,12 +46,16 @@ impl<'r> FromRequest<'r> for Auth {
         let parts: Vec<&str> = auth_header.split_whitespace().collect();
         let token = parts.last().unwrap_or(&"");
 
+        let database = request.rocket().state::<Database>().unwrap();
+
+        // Check if it is an OAuth2 access token allowed on this route
         if !utils::account::validate_token(token) {
-            return Outcome::Forward(Status::Unauthorized);
+            return match utils::oauth::authenticate(database, token, request.route()).await {
+                Some(user_id) => Outcome::Success(Auth(user_id)),
+                None => Outcome::Forward(Status::Unauthorized),
+            };
         }
 
-        let database = request.rocket().state::<Client>().unwrap();
-
         let user = database
             .query_one("SELECT * FROM users WHERE token = $1", &[&token])
             .await;
@@ -73,6 +79,17 @@ impl From<tokio_postgres::Error> for AppError {
     }
 }
 
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            return AppError(Status::NotFound);
+        }
+
+        println!("{:}", e);
+        AppError(Status::InternalServerError)
+    }
+}
+
 impl<'r> Responder<'r, 'static> for AppError {
     fn respond_to(self, _: &'r Request<'_>) -> Result<'static> {
         Err(self.0)
@@ -83,12 +100,34 @@ impl<'r> Responder<'r, 'static> for AppError {
 async fn rocket() -> _ {
     // Initialize
     dotenv::dotenv().ok();
-    let database = utils::database::connect().await.unwrap();
+    let database: Database = Arc::new(utils::database::connect().await.unwrap());
     let sse_clients: SSEClients = Arc::new(Mutex::new(vec![]));
+    let gateway_sessions: utils::gateway::GatewaySessions = Arc::new(Mutex::new(HashMap::new()));
+    let typing_states: routes::channels::TypingStates = Arc::new(Mutex::new(HashMap::new()));
+    let presences: utils::presence::Presences = Arc::new(Mutex::new(HashMap::new()));
+    let storage = utils::storage::from_env();
+    let dispatcher = utils::subscriptions::spawn(database.clone());
+    let unfurler = utils::unfurl::spawn(database.clone(), sse_clients.clone(), dispatcher.clone());
+    utils::messages::spawn(database.clone(), storage.clone());
+
+    // Allow attachment uploads
+    let limits = Limits::default()
+        .limit("file", routes::attachments::MAX_ATTACHMENT_SIZE.bytes())
+        .limit(
+            "data-form",
+            (routes::attachments::MAX_ATTACHMENT_SIZE + 1024 * 1024).bytes(),
+        );
 
     // Routes
-    rocket::build()
+    rocket::custom(rocket::Config::figment().merge(("limits", limits)))
         .manage(sse_clients)
+        .manage(gateway_sessions)
+        .manage(typing_states)
+        .manage(presences)
+        .manage(storage)
+        .manage(unfurler)
+        .manage(dispatcher)
+        .manage(routes::interactions::client())
         .manage(database)
         .mount("/", routes::get_routes())
 }
@@ -33,11 +33,14 @@ use uuid::Uuid;
 #[post("/signin", format = "json", data = "<body>")]
 async fn signin(
     body: Json<SigninBody>,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
 ) -> Result<Json<SigninResp>, AppError> {
     // Check if user exists
     let pre_user = database
-        .query_one("SELECT * FROM users WHERE email = $1", &[&body.email])
+        .query_one(
+            "SELECT * FROM users WHERE email = $1 AND type = 'USER'",
+            &[&body.email],
+        )
         .await;
 
     if pre_user.is_err() {
@@ -94,7 +97,7 @@ async fn signin(
 #[post("/signup", format = "json", data = "<body>")]
 async fn signup(
     body: Json<SignupBody>,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
 ) -> Result<Json<HashMap<String, String>>, AppError> {
     // Check if username is too long
     if body.username.len() > 30 {
@@ -154,7 +157,7 @@ async fn signup(
 #[post("/verify/<code>", format = "json")]
 async fn verify(
     code: &str,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
 ) -> Result<Json<SigninResp>, AppError> {
     // Check if user exists
     let pre_user = database
@@ -184,11 +187,14 @@ async fn verify(
 #[post("/reset/request", format = "json", data = "<body>")]
 async fn reset_request(
     body: Json<ResetRequestBody>,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
 ) -> Result<Json<HashMap<String, String>>, AppError> {
     // Check if user exists
     let pre_user = database
-        .query_one("SELECT * FROM users WHERE email = $1", &[&body.email])
+        .query_one(
+            "SELECT * FROM users WHERE email = $1 AND type = 'USER'",
+            &[&body.email],
+        )
         .await;
 
     if pre_user.is_err() {
@@ -210,7 +216,7 @@ async fn reset_request(
 }
 
 #[get("/reset/<code>", format = "json")]
-async fn reset_check(code: &str, database: &State<tokio_postgres::Client>) -> Result<(), AppError> {
+async fn reset_check(code: &str, database: &State<crate::Database>) -> Result<(), AppError> {
     // Check if user exists
     let pre_user = database
         .query_one("SELECT * FROM users WHERE verificator = $1", &[&code])
@@ -229,7 +235,7 @@ async fn reset_check(code: &str, database: &State<tokio_postgres::Client>) -> Re
 async fn reset(
     body: Json<ResetBody>,
     code: &str,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
 ) -> Result<Json<SigninResp>, AppError> {
     // Check if user exists
     let pre_user = database
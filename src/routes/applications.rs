@@ -0,0 +1,492 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::structs::{
+    AuthorizeApplicationBody, CreateApplicationBody, Member, PatchApplicationBody,
+    ReturnedApplication, ReturnedGuild, ReturnedUser,
+};
+use crate::{
+    utils::{
+        self,
+        permissions::{check_guild_permission, GuildPermissions},
+    },
+    AppError, Auth,
+};
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
+    Argon2,
+};
+use rand::Rng;
+use rocket::{
+    http::Status,
+    serde::json::{serde_json, to_value, Json, Value},
+    Route, State,
+};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+// Maximum amount of bots owned by a user
+const MAX_APPLICATIONS: usize = 25;
+// Maximum amount of OAuth2 redirect URIs per application
+const MAX_REDIRECT_URIS: usize = 10;
+
+fn get_returned_application(bot: &Row, token: Option<String>) -> ReturnedApplication {
+    ReturnedApplication {
+        id: bot.get::<&str, Uuid>("id").to_string(),
+        owner: bot.get::<&str, String>("owner"),
+        bot: ReturnedUser {
+            id: bot.get::<&str, Uuid>("id").to_string(),
+            username: bot.get::<&str, String>("username"),
+            discriminator: bot.get::<&str, String>("discriminator"),
+            avatar: bot
+                .try_get::<&str, Option<String>>("avatar")
+                .unwrap_or(None),
+            about: bot.try_get::<&str, Option<String>>("about").unwrap_or(None),
+            creation: bot.get::<&str, i64>("creation"),
+            bot: true,
+        },
+        interactions_url: bot
+            .try_get::<&str, Option<String>>("interactions_url")
+            .unwrap_or(None),
+        redirect_uris: bot
+            .try_get::<&str, Option<Vec<String>>>("redirect_uris")
+            .unwrap_or(None)
+            .unwrap_or_default(),
+        token,
+        interactions_secret: None,
+    }
+}
+
+// Get a bot owned by the user
+async fn get_owned_bot(
+    database: &tokio_postgres::Client,
+    application_id: &str,
+    user_id: &str,
+) -> Result<Row, AppError> {
+    let pre_bot = database
+        .query_one(
+            "SELECT * FROM users WHERE id = $1 AND type = 'BOT' AND owner = $2",
+            &[&Uuid::parse_str(application_id).unwrap(), &user_id],
+        )
+        .await;
+
+    if pre_bot.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    Ok(pre_bot.unwrap())
+}
+
+#[get("/applications", format = "json")]
+async fn get_applications(
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<ReturnedApplication>>, AppError> {
+    let bots = database
+        .query(
+            "SELECT * FROM users WHERE type = 'BOT' AND owner = $1 ORDER BY creation",
+            &[&user_id.0],
+        )
+        .await?;
+
+    Ok(Json(
+        bots.iter()
+            .map(|bot| get_returned_application(bot, None))
+            .collect(),
+    ))
+}
+
+#[post("/applications", format = "json", data = "<body>")]
+async fn create_application(
+    body: Json<CreateApplicationBody>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedApplication>, AppError> {
+    // Check if username is empty or too long
+    if body.username.trim().is_empty() || body.username.len() > 30 {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Check if the user isn't a bot
+    if database
+        .query_one(
+            "SELECT * FROM users WHERE id = $1 AND type = 'USER'",
+            &[&Uuid::parse_str(&user_id.0).unwrap()],
+        )
+        .await
+        .is_err()
+    {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Check if the user owns too many bots
+    if database
+        .query(
+            "SELECT id FROM users WHERE type = 'BOT' AND owner = $1",
+            &[&user_id.0],
+        )
+        .await?
+        .len()
+        >= MAX_APPLICATIONS
+    {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Get all users with same username
+    let same_usernames = database
+        .query("SELECT * FROM users WHERE username = $1", &[&body.username])
+        .await?;
+
+    // Generate discriminator
+    let discriminator = utils::account::generate_discriminator(
+        &same_usernames
+            .iter()
+            .map(|row| row.get::<&str, String>("discriminator"))
+            .collect::<Vec<String>>(),
+    );
+
+    // All discriminators are taken
+    if discriminator.is_none() {
+        return Err(AppError(Status::Conflict));
+    }
+
+    // Create bot, it can't sign in so its password is never known
+    let id = Uuid::new_v4();
+    let password = Argon2::default()
+        .hash_password(
+            Uuid::new_v4().to_string().as_bytes(),
+            &SaltString::generate(&mut OsRng),
+        )
+        .unwrap()
+        .to_string();
+    let token = utils::account::generate_bot_token(id.to_string()).unwrap();
+
+    let bot = database.query_one("INSERT INTO users (id, token, email, password, username, discriminator, avatar, creation, type, owner, verified, verificator) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) RETURNING *", &[&id, &token, &format!("{}@bot.invalid", id), &password, &body.username, &discriminator, &"userDefault", &(SystemTime::now()
+    .duration_since(UNIX_EPOCH).unwrap().as_secs() as i64), &"BOT", &user_id.0, &true, &""]).await?;
+
+    Ok(Json(get_returned_application(&bot, Some(token))))
+}
+
+#[get("/applications/<application_id>", format = "json")]
+async fn get_application(
+    application_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedApplication>, AppError> {
+    let bot = get_owned_bot(database, application_id, &user_id.0).await?;
+
+    Ok(Json(get_returned_application(&bot, None)))
+}
+
+#[patch("/applications/<application_id>", format = "json", data = "<body>")]
+async fn patch_application(
+    application_id: &str,
+    body: Json<PatchApplicationBody>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedApplication>, AppError> {
+    // An empty URL removes it, interactions are then sent over the event stream
+    let interactions_url = body
+        .interactions_url
+        .as_ref()
+        .filter(|interactions_url| !interactions_url.is_empty());
+
+    // Check if URLs are valid
+    if interactions_url
+        .is_some_and(|interactions_url| !utils::network::is_allowed_url(interactions_url))
+        || body.redirect_uris.as_ref().is_some_and(|redirect_uris| {
+            redirect_uris.len() > MAX_REDIRECT_URIS
+                || !redirect_uris
+                    .iter()
+                    .all(|redirect_uri| utils::oauth::is_valid_redirect_uri(redirect_uri))
+        })
+    {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    let mut bot = get_owned_bot(database, application_id, &user_id.0).await?;
+    let mut interactions_secret = None;
+
+    if body.interactions_url.is_some() {
+        // A new secret is generated every time the URL changes
+        interactions_secret =
+            interactions_url.map(|_| hex::encode(rand::rng().random::<[u8; 32]>()));
+
+        bot = database
+            .query_one(
+                "UPDATE users SET interactions_url = $1, interactions_secret = $2 WHERE id = $3 RETURNING *",
+                &[
+                    &interactions_url,
+                    &interactions_secret,
+                    &Uuid::parse_str(application_id).unwrap(),
+                ],
+            )
+            .await?;
+    }
+
+    if body.redirect_uris.is_some() {
+        bot = database
+            .query_one(
+                "UPDATE users SET redirect_uris = $1 WHERE id = $2 RETURNING *",
+                &[
+                    &body.redirect_uris,
+                    &Uuid::parse_str(application_id).unwrap(),
+                ],
+            )
+            .await?;
+    }
+
+    Ok(Json(ReturnedApplication {
+        interactions_secret,
+        ..get_returned_application(&bot, None)
+    }))
+}
+
+#[delete("/applications/<application_id>", format = "json")]
+async fn del_application(
+    application_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    get_owned_bot(database, application_id, &user_id.0).await?;
+
+    // Remove the bot from its guilds
+    database
+        .execute(
+            "UPDATE guilds SET members = array_remove(members, (
+            SELECT member
+            FROM unnest(members) AS member
+            WHERE member->>'id' = $1
+            )) WHERE EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $1
+           )",
+            &[&application_id],
+        )
+        .await?;
+
+    // Delete its commands
+    database
+        .execute(
+            "DELETE FROM commands WHERE application = $1",
+            &[&application_id],
+        )
+        .await?;
+
+    // Revoke its authorizations
+    database
+        .execute(
+            "DELETE FROM oauth_codes WHERE client = $1",
+            &[&application_id],
+        )
+        .await?;
+
+    database
+        .execute(
+            "DELETE FROM oauth_consents WHERE client = $1",
+            &[&application_id],
+        )
+        .await?;
+
+    database
+        .execute(
+            "DELETE FROM oauth_tokens WHERE client = $1",
+            &[&application_id],
+        )
+        .await?;
+
+    // Delete bot
+    database
+        .execute(
+            "DELETE FROM users WHERE id = $1 AND type = 'BOT'",
+            &[&Uuid::parse_str(application_id).unwrap()],
+        )
+        .await?;
+
+    Ok(Json(HashMap::new()))
+}
+
+#[post("/applications/<application_id>/token", format = "json")]
+async fn regenerate_application_token(
+    application_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedApplication>, AppError> {
+    let bot = get_owned_bot(database, application_id, &user_id.0).await?;
+
+    // Replacing the token invalidates the previous one
+    let token = utils::account::generate_bot_token(application_id.to_string()).unwrap();
+    database
+        .execute(
+            "UPDATE users SET token = $1 WHERE id = $2",
+            &[&token, &Uuid::parse_str(application_id).unwrap()],
+        )
+        .await?;
+
+    Ok(Json(get_returned_application(&bot, Some(token))))
+}
+
+#[get("/applications/<application_id>/authorize", format = "json")]
+async fn get_application_authorization(
+    application_id: &str,
+    database: &State<crate::Database>,
+    _user_id: Auth,
+) -> Result<Json<ReturnedApplication>, AppError> {
+    // Get bot
+    let pre_bot = database
+        .query_one(
+            "SELECT * FROM users WHERE id = $1 AND type = 'BOT'",
+            &[&Uuid::parse_str(application_id).unwrap()],
+        )
+        .await;
+
+    if pre_bot.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    Ok(Json(get_returned_application(&pre_bot.unwrap(), None)))
+}
+
+#[post(
+    "/applications/<application_id>/authorize",
+    format = "json",
+    data = "<body>"
+)]
+async fn authorize_application(
+    application_id: &str,
+    body: Json<AuthorizeApplicationBody>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedGuild>, AppError> {
+    // Check if bot exists
+    if database
+        .query_one(
+            "SELECT * FROM users WHERE id = $1 AND type = 'BOT'",
+            &[&Uuid::parse_str(application_id).unwrap()],
+        )
+        .await
+        .is_err()
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(&body.guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if can manage the guild
+    if !check_guild_permission(&guild, &user_id.0, GuildPermissions::MANAGE_GUILD) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Check if the bot is banned
+    if guild
+        .get::<&str, Vec<Value>>("bans")
+        .contains(&to_value(application_id).unwrap())
+    {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Append member, if not already one
+    if database
+        .execute(
+            "UPDATE guilds SET members = array_append(members, $1) WHERE id = $2 AND NOT EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $3
+           )",
+            &[
+                &to_value(Member {
+                    id: application_id.to_string(),
+                    nickname: None,
+                    roles: vec!["11111111-1111-1111-1111-111111111111".to_string()],
+                })
+                .unwrap(),
+                &guild.get::<&str, Uuid>("id"),
+                &application_id,
+            ],
+        )
+        .await?
+        < 1
+    {
+        return Err(AppError(Status::Conflict));
+    }
+
+    let returned_guild = ReturnedGuild {
+        id: guild.get::<&str, Uuid>("id").to_string(),
+        name: guild.get::<&str, String>("name"),
+        description: guild
+            .try_get::<&str, Option<String>>("description")
+            .unwrap_or(None),
+        icon: guild
+            .try_get::<&str, Option<String>>("icon")
+            .unwrap_or(None),
+        public: guild.get::<&str, bool>("public"),
+        roles: serde_json::from_value(Value::Array(guild.get::<&str, Vec<Value>>("roles")))
+            .unwrap(),
+        members: guild.get::<&str, Vec<Value>>("members").len() + 1,
+        creation: guild.get::<&str, i64>("creation"),
+    };
+
+    // Broadcast guildJoined event to the bot
+    utils::sse::broadcast(
+        sse_clients,
+        application_id,
+        utils::structs::SSEEvent::GuildJoined {
+            guild: &returned_guild,
+        },
+    )
+    .await;
+
+    Ok(Json(returned_guild))
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![
+        get_applications,
+        create_application,
+        get_application,
+        patch_application,
+        del_application,
+        regenerate_application_token,
+        get_application_authorization,
+        authorize_application
+    ]
+}
@@ -0,0 +1,498 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::structs::{Channel, Message, UploadAttachmentForm};
+use crate::{
+    routes::{
+        channels::{stop_typing, TypingStates},
+        dms::get_sendable_dm_recipients,
+    },
+    utils::{
+        self,
+        messages::Destination,
+        permissions::{check_channel_permission, ChannelPermissions},
+        storage::StorageBackend,
+        subscriptions::Dispatcher,
+        unfurl::Unfurler,
+    },
+    AppError, Auth,
+};
+
+use rocket::{
+    form::Form,
+    fs::TempFile,
+    http::{ContentType, Header, Status},
+    request::Request,
+    response::{self, Responder, Response},
+    serde::json::{from_value, Json, Value},
+    tokio::io::AsyncReadExt,
+    Route, State,
+};
+use std::{
+    io::Cursor,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+// Maximum size of an attachment, in bytes
+pub const MAX_ATTACHMENT_SIZE: u64 = 25 * 1024 * 1024;
+
+// Sniffed content types that can be uploaded
+const ALLOWED_CONTENT_TYPES: [&str; 16] = [
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "video/mp4",
+    "video/webm",
+    "video/quicktime",
+    "audio/mpeg",
+    "audio/ogg",
+    "audio/x-wav",
+    "audio/x-flac",
+    "application/pdf",
+    "application/zip",
+    "application/gzip",
+    "application/x-7z-compressed",
+    "text/plain",
+];
+
+struct StoredAttachment {
+    id: Uuid,
+    filename: String,
+    content_type: String,
+    size: i64,
+}
+
+// Read, sniff and store an uploaded file
+async fn store_attachment(
+    storage: &StorageBackend,
+    file: &TempFile<'_>,
+    channel_id: &str,
+) -> Result<StoredAttachment, AppError> {
+    // Check if file is empty or too large
+    if file.len() == 0 {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    if file.len() > MAX_ATTACHMENT_SIZE {
+        return Err(AppError(Status::PayloadTooLarge));
+    }
+
+    let mut data = Vec::new();
+    file.open().await?.read_to_end(&mut data).await?;
+
+    // Sniff the content type instead of trusting the client
+    let (content_type, extension) = match infer::get(&data) {
+        Some(kind) => (kind.mime_type(), kind.extension()),
+        None if std::str::from_utf8(&data).is_ok() => ("text/plain", "txt"),
+        None => return Err(AppError(Status::UnsupportedMediaType)),
+    };
+
+    if !ALLOWED_CONTENT_TYPES.contains(&content_type) {
+        return Err(AppError(Status::UnsupportedMediaType));
+    }
+
+    let attachment = StoredAttachment {
+        id: Uuid::new_v4(),
+        filename: format!("{}.{}", file.name().unwrap_or("file"), extension),
+        content_type: content_type.to_string(),
+        size: data.len() as i64,
+    };
+
+    storage
+        .put(
+            &format!(
+                "attachments/{}/{}/{}",
+                channel_id, attachment.id, attachment.filename
+            ),
+            &data,
+            &attachment.content_type,
+        )
+        .await?;
+
+    Ok(attachment)
+}
+
+// Create the message holding the stored attachment
+async fn create_attachment_message(
+    storage: &StorageBackend,
+    database: &tokio_postgres::Client,
+    channel_id: &str,
+    guild_id: Option<&str>,
+    message: Message,
+    attachment: StoredAttachment,
+) -> Result<Message, AppError> {
+    let key = format!(
+        "attachments/{}/{}/{}",
+        channel_id, attachment.id, attachment.filename
+    );
+
+    let result =
+        insert_attachment_message(database, channel_id, guild_id, message, attachment).await;
+
+    // Remove the stored file if the message couldn't be created
+    if result.is_err() {
+        storage.delete(&key).await?;
+    }
+
+    result
+}
+
+async fn insert_attachment_message(
+    database: &tokio_postgres::Client,
+    channel_id: &str,
+    guild_id: Option<&str>,
+    message: Message,
+    attachment: StoredAttachment,
+) -> Result<Message, AppError> {
+    let message = utils::messages::create_message(
+        database,
+        channel_id,
+        guild_id,
+        Message {
+            atachment: Some(attachment.filename.clone()),
+            atachment_id: Some(attachment.id.to_string()),
+            ..message
+        },
+    )
+    .await?;
+
+    database.execute("INSERT INTO attachments (id, channel, guild, message, filename, content_type, size, creation) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    &[
+        &attachment.id,
+        &Uuid::parse_str(channel_id).unwrap(),
+        &guild_id.map(|guild_id| Uuid::parse_str(guild_id).unwrap()),
+        &Uuid::parse_str(&message.id).unwrap(),
+        &attachment.filename,
+        &attachment.content_type,
+        &attachment.size,
+        &(SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64),
+    ]).await?;
+
+    Ok(message)
+}
+
+#[post(
+    "/guilds/<guild_id>/channels/<channel_id>/attachments",
+    format = "multipart/form-data",
+    data = "<body>"
+)]
+#[allow(clippy::too_many_arguments)]
+async fn upload_guild_attachment(
+    guild_id: &str,
+    channel_id: &str,
+    body: Form<UploadAttachmentForm<'_>>,
+    storage: &State<StorageBackend>,
+    typing_states: &State<TypingStates>,
+    unfurler: &State<Unfurler>,
+    dispatcher: &State<Dispatcher>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Message>, AppError> {
+    let content = body.content.clone().unwrap_or_default();
+
+    // Check if content is too long
+    if content.len() > utils::messages::MAX_CONTENT_LENGTH {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    if !channels.iter().any(|channel| channel.id == channel_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if can send messages
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::SEND_MESSAGES,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Check if the channel isn't an archived thread
+    if channels.iter().any(|channel| {
+        channel.id == channel_id
+            && channel
+                .thread
+                .as_ref()
+                .is_some_and(|thread| thread.archived)
+    }) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    let attachment = store_attachment(storage, &body.file, channel_id).await?;
+    let message = create_attachment_message(
+        storage,
+        database,
+        channel_id,
+        Some(guild_id),
+        utils::messages::resolve_guild_mentions(
+            &guild,
+            check_channel_permission(
+                &guild,
+                &channel_id.to_string(),
+                &user_id.0,
+                ChannelPermissions::MENTION_EVERYONE,
+            ),
+            utils::messages::new_message(&user_id.0, &content, "default"),
+        ),
+        attachment,
+    )
+    .await?;
+
+    stop_typing(typing_states, sse_clients, channel_id, &user_id.0).await;
+
+    utils::messages::send_message(
+        database,
+        sse_clients,
+        dispatcher,
+        unfurler,
+        Destination::Guild(&guild),
+        channel_id,
+        &message,
+    )
+    .await?;
+
+    Ok(Json(message))
+}
+
+#[post(
+    "/channels/<channel_id>/attachments",
+    format = "multipart/form-data",
+    data = "<body>"
+)]
+#[allow(clippy::too_many_arguments)]
+async fn upload_dm_attachment(
+    channel_id: &str,
+    body: Form<UploadAttachmentForm<'_>>,
+    storage: &State<StorageBackend>,
+    dispatcher: &State<Dispatcher>,
+    unfurler: &State<Unfurler>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Message>, AppError> {
+    let content = body.content.clone().unwrap_or_default();
+
+    // Check if content is too long
+    if content.len() > utils::messages::MAX_CONTENT_LENGTH {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    let recipients = get_sendable_dm_recipients(database, channel_id, &user_id.0).await?;
+
+    let attachment = store_attachment(storage, &body.file, channel_id).await?;
+    let message = create_attachment_message(
+        storage,
+        database,
+        channel_id,
+        None,
+        utils::messages::resolve_dm_mentions(
+            &recipients,
+            utils::messages::new_message(&user_id.0, &content, "default"),
+        ),
+        attachment,
+    )
+    .await?;
+
+    utils::messages::send_message(
+        database,
+        sse_clients,
+        dispatcher,
+        unfurler,
+        Destination::Dm(&recipients),
+        channel_id,
+        &message,
+    )
+    .await?;
+
+    Ok(Json(message))
+}
+
+pub struct AttachmentResponse {
+    data: Vec<u8>,
+    content_type: String,
+    filename: String,
+}
+
+impl<'r> Responder<'r, 'static> for AttachmentResponse {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        // Only media is displayed inline, everything else is downloaded
+        let disposition = if ["image/", "video/", "audio/"]
+            .iter()
+            .any(|prefix| self.content_type.starts_with(prefix))
+        {
+            "inline"
+        } else {
+            "attachment"
+        };
+
+        Response::build()
+            .header(ContentType::parse_flexible(&self.content_type).unwrap_or(ContentType::Binary))
+            .header(Header::new(
+                "Content-Disposition",
+                format!("{}; filename=\"{}\"", disposition, self.filename),
+            ))
+            .header(Header::new("X-Content-Type-Options", "nosniff"))
+            .sized_body(self.data.len(), Cursor::new(self.data))
+            .ok()
+    }
+}
+
+#[get("/attachments/<attachment_id>/<filename>")]
+async fn get_attachment(
+    attachment_id: &str,
+    filename: &str,
+    storage: &State<StorageBackend>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<AttachmentResponse, AppError> {
+    // Get attachment, with whether its message was deleted
+    let pre_attachment = database
+        .query_one(
+            "SELECT attachments.*, messages.deleted FROM attachments
+            LEFT JOIN messages ON messages.id = attachments.message
+            WHERE attachments.id = $1 AND attachments.filename = $2",
+            &[&Uuid::parse_str(attachment_id).unwrap(), &filename],
+        )
+        .await;
+
+    if pre_attachment.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let attachment = pre_attachment.unwrap();
+    let channel_id = attachment.get::<&str, Uuid>("channel").to_string();
+    let deleted = attachment.get::<&str, Option<i64>>("deleted").is_some();
+
+    match attachment.get::<&str, Option<Uuid>>("guild") {
+        Some(guild_id) => {
+            // Get guild
+            let pre_guild = database
+                .query_one(
+                    "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+                       SELECT 1
+                       FROM unnest(members) AS member
+                       WHERE member->>'id' = $2
+                   )",
+                    &[&guild_id, &user_id.0],
+                )
+                .await;
+
+            if pre_guild.is_err() {
+                return Err(AppError(Status::NotFound));
+            }
+
+            let guild = pre_guild.unwrap();
+
+            // Check if the channel still exists
+            let channels: Vec<Channel> =
+                from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+            if !channels.iter().any(|channel| channel.id == channel_id) {
+                return Err(AppError(Status::NotFound));
+            }
+
+            // Check if can view the channel
+            if !check_channel_permission(
+                &guild,
+                &channel_id,
+                &user_id.0,
+                ChannelPermissions::VIEW_CHANNEL,
+            ) {
+                return Err(AppError(Status::Forbidden));
+            }
+
+            // Only moderators can see the attachments of deleted messages
+            if deleted
+                && !check_channel_permission(
+                    &guild,
+                    &channel_id,
+                    &user_id.0,
+                    ChannelPermissions::MANAGE_MESSAGES,
+                )
+            {
+                return Err(AppError(Status::NotFound));
+            }
+        }
+        None => {
+            // Check if a recipient of the DM, and the message wasn't deleted
+            if deleted
+                || database
+                    .query_one(
+                        "SELECT * FROM dms WHERE id = $1 AND $2 = any(recipients)",
+                        &[&Uuid::parse_str(&channel_id).unwrap(), &user_id.0],
+                    )
+                    .await
+                    .is_err()
+            {
+                return Err(AppError(Status::NotFound));
+            }
+        }
+    }
+
+    let data = storage
+        .get(&format!(
+            "attachments/{}/{}/{}",
+            channel_id, attachment_id, filename
+        ))
+        .await?;
+
+    Ok(AttachmentResponse {
+        data,
+        content_type: attachment.get::<&str, String>("content_type"),
+        filename: filename.to_string(),
+    })
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![
+        upload_guild_attachment,
+        upload_dm_attachment,
+        get_attachment
+    ]
+}
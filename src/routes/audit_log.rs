@@ -0,0 +1,109 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::structs::AuditLogEntry;
+use crate::{
+    utils::{
+        self,
+        permissions::{check_guild_permission, GuildPermissions},
+    },
+    AppError, Auth,
+};
+
+use rocket::{
+    http::Status,
+    serde::json::{Json, Value},
+    Route, State,
+};
+use uuid::Uuid;
+
+#[get(
+    "/guilds/<guild_id>/audit-log?<action>&<before>&<limit>",
+    format = "json"
+)]
+async fn get_audit_log(
+    guild_id: &str,
+    action: Option<&str>,
+    before: Option<&str>,
+    limit: Option<i64>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<AuditLogEntry>>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if can view the audit log
+    if !check_guild_permission(
+        &pre_guild.unwrap(),
+        &user_id.0,
+        GuildPermissions::VIEW_AUDIT_LOG,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Get entries, newest first
+    let entries = database
+        .query(
+            "SELECT * FROM audit_log WHERE guild = $1
+            AND ($2::text IS NULL OR action = $2)
+            AND ($3::uuid IS NULL OR (creation, id) < (
+                SELECT creation, id FROM audit_log WHERE id = $3
+            )) ORDER BY creation DESC, id DESC LIMIT $4",
+            &[
+                &Uuid::parse_str(guild_id).unwrap(),
+                &action,
+                &before.and_then(|before| Uuid::parse_str(before).ok()),
+                &limit
+                    .unwrap_or(utils::messages::DEFAULT_LIMIT)
+                    .clamp(1, utils::messages::MAX_LIMIT),
+            ],
+        )
+        .await?;
+
+    Ok(Json(
+        entries
+            .iter()
+            .map(|entry| AuditLogEntry {
+                id: entry.get::<&str, Uuid>("id").to_string(),
+                author: entry.get::<&str, String>("author"),
+                action: entry.get::<&str, String>("action"),
+                target: entry.get::<&str, Option<String>>("target"),
+                details: entry.get::<&str, Value>("details"),
+                creation: entry.get::<&str, i64>("creation"),
+            })
+            .collect(),
+    ))
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![get_audit_log]
+}
@@ -0,0 +1,241 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::structs::{Channel, Member};
+use crate::{
+    utils::{
+        self,
+        permissions::{check_channel_permission, ChannelPermissions},
+    },
+    AppError, Auth,
+};
+
+use rocket::{
+    http::Status,
+    serde::json::{from_value, Json, Value},
+    tokio::{
+        self,
+        sync::Mutex,
+        time::{sleep_until, Duration, Instant},
+    },
+    Route, State,
+};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+// Seconds until a typing indicator expires
+const TYPING_TIMEOUT: u64 = 10;
+// Seconds during which repeated typing requests aren't broadcasted again
+const TYPING_DEBOUNCE: u64 = 5;
+
+// Typing users, by (channel, user)
+pub type TypingStates = Arc<Mutex<HashMap<(String, String), TypingState>>>;
+
+pub struct TypingState {
+    // Identifies the expiry task, which only acts on its own typing indicator
+    id: Uuid,
+    guild_id: String,
+    recipients: Vec<String>,
+    broadcasted: Instant,
+    expires: Instant,
+}
+
+#[post("/guilds/<guild_id>/channels/<channel_id>/typing", format = "json")]
+async fn start_typing(
+    guild_id: &str,
+    channel_id: &str,
+    typing_states: &State<TypingStates>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    if !channels.iter().any(|channel| channel.id == channel_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if can send messages
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::SEND_MESSAGES,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    let key = (channel_id.to_string(), user_id.0.clone());
+    let now = Instant::now();
+    let mut typing_lock = typing_states.lock().await;
+
+    // Get every other member that can view the channel
+    let members: Vec<Member> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("members"))).unwrap();
+    let recipients: Vec<String> = members
+        .into_iter()
+        .filter(|member| {
+            member.id != user_id.0
+                && check_channel_permission(
+                    &guild,
+                    &channel_id.to_string(),
+                    &member.id,
+                    ChannelPermissions::VIEW_CHANNEL,
+                )
+        })
+        .map(|member| member.id)
+        .collect();
+
+    if let Some(state) = typing_lock.get_mut(&key) {
+        // Already typing, only extend the expiry unless debounced
+        state.expires = now + Duration::from_secs(TYPING_TIMEOUT);
+        state.recipients = recipients.clone();
+
+        if now < state.broadcasted + Duration::from_secs(TYPING_DEBOUNCE) {
+            return Ok(Json(HashMap::new()));
+        }
+
+        state.broadcasted = now;
+    } else {
+        let id = Uuid::new_v4();
+        typing_lock.insert(
+            key.clone(),
+            TypingState {
+                id,
+                guild_id: guild_id.to_string(),
+                recipients: recipients.clone(),
+                broadcasted: now,
+                expires: now + Duration::from_secs(TYPING_TIMEOUT),
+            },
+        );
+
+        // Expire the typing indicator once it's no longer refreshed
+        let typing_states = typing_states.inner().clone();
+        let sse_clients = sse_clients.inner().clone();
+
+        tokio::spawn(async move {
+            loop {
+                let mut typing_lock = typing_states.lock().await;
+                let expires = match typing_lock.get(&key) {
+                    Some(state) if state.id == id => state.expires,
+                    // Stopped, or replaced by a newer typing indicator
+                    _ => return,
+                };
+
+                if Instant::now() >= expires {
+                    let state = typing_lock.remove(&key).unwrap();
+                    drop(typing_lock);
+
+                    broadcast_typing_stopped(&sse_clients, &key, &state).await;
+                    return;
+                }
+
+                drop(typing_lock);
+                sleep_until(expires).await;
+            }
+        });
+    }
+
+    drop(typing_lock);
+
+    // Broadcast typingStarted event to every other member that can view the channel
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+
+    for recipient in recipients {
+        utils::sse::broadcast(
+            sse_clients,
+            &recipient,
+            utils::structs::SSEEvent::TypingStarted {
+                guild_id,
+                channel_id,
+                user_id: &user_id.0,
+                timestamp,
+            },
+        )
+        .await;
+    }
+
+    Ok(Json(HashMap::new()))
+}
+
+// Broadcast typingStopped event to every other member that can view the channel
+async fn broadcast_typing_stopped(
+    sse_clients: &crate::SSEClients,
+    key: &(String, String),
+    state: &TypingState,
+) {
+    for recipient in &state.recipients {
+        utils::sse::broadcast(
+            sse_clients,
+            recipient,
+            utils::structs::SSEEvent::TypingStopped {
+                guild_id: &state.guild_id,
+                channel_id: &key.0,
+                user_id: &key.1,
+            },
+        )
+        .await;
+    }
+}
+
+// Clear the user's typing indicator, e.g. once their message is sent
+pub async fn stop_typing(
+    typing_states: &TypingStates,
+    sse_clients: &crate::SSEClients,
+    channel_id: &str,
+    user_id: &str,
+) {
+    let key = (channel_id.to_string(), user_id.to_string());
+    let state = typing_states.lock().await.remove(&key);
+
+    if let Some(state) = state {
+        broadcast_typing_stopped(sse_clients, &key, &state).await;
+    }
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![start_typing]
+}
@@ -0,0 +1,359 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::structs::{CommandOption, CreateCommandBody, Member, ReturnedCommand};
+use crate::{AppError, Auth};
+
+use rocket::{
+    http::Status,
+    serde::json::{from_value, to_value, Json, Value},
+    Route, State,
+};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+// Maximum amount of commands per application, globally and in each guild
+const MAX_COMMANDS: usize = 100;
+// Maximum amount of options per command
+const MAX_OPTIONS: usize = 25;
+// Maximum length of names and descriptions
+const MAX_NAME_LENGTH: usize = 32;
+const MAX_DESCRIPTION_LENGTH: usize = 100;
+// Types an option's value can have
+const OPTION_TYPES: [&str; 6] = ["string", "integer", "number", "boolean", "user", "channel"];
+
+pub fn get_returned_command(command: &Row) -> ReturnedCommand {
+    ReturnedCommand {
+        id: command.get::<&str, Uuid>("id").to_string(),
+        application_id: command.get::<&str, String>("application"),
+        guild_id: command
+            .get::<&str, Option<Uuid>>("guild")
+            .map(|guild| guild.to_string()),
+        name: command.get::<&str, String>("name"),
+        description: command.get::<&str, String>("description"),
+        options: from_value(Value::Array(command.get::<&str, Vec<Value>>("options"))).unwrap(),
+        creation: command.get::<&str, i64>("creation"),
+    }
+}
+
+// Check if the name only has lowercase letters, digits, dashes and underscores
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name.chars().all(|char| {
+            char.is_ascii_lowercase() || char.is_ascii_digit() || char == '-' || char == '_'
+        })
+}
+
+fn is_valid_description(description: &str) -> bool {
+    !description.trim().is_empty() && description.len() <= MAX_DESCRIPTION_LENGTH
+}
+
+fn is_valid_command(body: &CreateCommandBody) -> bool {
+    let mut names: Vec<&str> = body
+        .options
+        .iter()
+        .map(|option| option.name.as_str())
+        .collect();
+    names.sort();
+    names.dedup();
+
+    is_valid_name(&body.name)
+        && is_valid_description(&body.description)
+        && body.options.len() <= MAX_OPTIONS
+        && names.len() == body.options.len()
+        && body.options.iter().all(|option: &CommandOption| {
+            is_valid_name(&option.name)
+                && is_valid_description(&option.description)
+                && OPTION_TYPES.contains(&option.r#type.as_str())
+        })
+}
+
+// Check if the user is the bot itself or its owner
+async fn check_application_access(
+    database: &tokio_postgres::Client,
+    application_id: &str,
+    user_id: &str,
+) -> Result<(), AppError> {
+    if database
+        .query_one(
+            "SELECT * FROM users WHERE id = $1 AND type = 'BOT' AND (id::text = $2 OR owner = $2)",
+            &[&Uuid::parse_str(application_id).unwrap(), &user_id],
+        )
+        .await
+        .is_err()
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    Ok(())
+}
+
+// Register a command, replacing the one with the same name
+async fn upsert_command(
+    database: &tokio_postgres::Client,
+    application_id: &str,
+    guild_id: Option<Uuid>,
+    body: &CreateCommandBody,
+) -> Result<ReturnedCommand, AppError> {
+    let existing = database
+        .query(
+            "SELECT * FROM commands WHERE application = $1 AND guild IS NOT DISTINCT FROM $2",
+            &[&application_id, &guild_id],
+        )
+        .await?;
+
+    let same_name = existing
+        .iter()
+        .find(|command| command.get::<&str, String>("name") == body.name);
+
+    // Check if the application has too many commands
+    if same_name.is_none() && existing.len() >= MAX_COMMANDS {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    let options: Vec<Value> = body
+        .options
+        .iter()
+        .map(|option| to_value(option).unwrap())
+        .collect();
+
+    let command = match same_name {
+        Some(command) => {
+            database
+                .query_one(
+                    "UPDATE commands SET description = $1, options = $2 WHERE id = $3 RETURNING *",
+                    &[&body.description, &options, &command.get::<&str, Uuid>("id")],
+                )
+                .await?
+        }
+        None => {
+            database.query_one("INSERT INTO commands (id, application, guild, name, description, options, creation) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *",
+            &[
+                &Uuid::new_v4(),
+                &application_id,
+                &guild_id,
+                &body.name,
+                &body.description,
+                &options,
+                &(SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs() as i64),
+            ]).await?
+        }
+    };
+
+    Ok(get_returned_command(&command))
+}
+
+#[get("/applications/<application_id>/commands", format = "json")]
+async fn get_global_commands(
+    application_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<ReturnedCommand>>, AppError> {
+    check_application_access(database, application_id, &user_id.0).await?;
+
+    let commands = database
+        .query(
+            "SELECT * FROM commands WHERE application = $1 AND guild IS NULL ORDER BY name",
+            &[&application_id],
+        )
+        .await?;
+
+    Ok(Json(commands.iter().map(get_returned_command).collect()))
+}
+
+#[post(
+    "/applications/<application_id>/commands",
+    format = "json",
+    data = "<body>"
+)]
+async fn create_global_command(
+    application_id: &str,
+    body: Json<CreateCommandBody>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedCommand>, AppError> {
+    // Check if command is valid
+    if !is_valid_command(&body) {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    check_application_access(database, application_id, &user_id.0).await?;
+
+    Ok(Json(
+        upsert_command(database, application_id, None, &body).await?,
+    ))
+}
+
+#[get(
+    "/applications/<application_id>/guilds/<guild_id>/commands",
+    format = "json"
+)]
+async fn get_guild_commands(
+    application_id: &str,
+    guild_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<ReturnedCommand>>, AppError> {
+    check_application_access(database, application_id, &user_id.0).await?;
+
+    let commands = database
+        .query(
+            "SELECT * FROM commands WHERE application = $1 AND guild = $2 ORDER BY name",
+            &[&application_id, &Uuid::parse_str(guild_id).unwrap()],
+        )
+        .await?;
+
+    Ok(Json(commands.iter().map(get_returned_command).collect()))
+}
+
+#[post(
+    "/applications/<application_id>/guilds/<guild_id>/commands",
+    format = "json",
+    data = "<body>"
+)]
+async fn create_guild_command(
+    application_id: &str,
+    guild_id: &str,
+    body: Json<CreateCommandBody>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedCommand>, AppError> {
+    // Check if command is valid
+    if !is_valid_command(&body) {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    check_application_access(database, application_id, &user_id.0).await?;
+
+    // Check if the bot is in the guild
+    if database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &application_id],
+        )
+        .await
+        .is_err()
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    Ok(Json(
+        upsert_command(
+            database,
+            application_id,
+            Some(Uuid::parse_str(guild_id).unwrap()),
+            &body,
+        )
+        .await?,
+    ))
+}
+
+#[delete(
+    "/applications/<application_id>/commands/<command_id>",
+    format = "json"
+)]
+async fn del_command(
+    application_id: &str,
+    command_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    check_application_access(database, application_id, &user_id.0).await?;
+
+    // Delete command
+    if database
+        .execute(
+            "DELETE FROM commands WHERE id = $1 AND application = $2",
+            &[&Uuid::parse_str(command_id).unwrap(), &application_id],
+        )
+        .await?
+        < 1
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    Ok(Json(HashMap::new()))
+}
+
+#[get("/guilds/<guild_id>/commands", format = "json")]
+async fn get_available_commands(
+    guild_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<ReturnedCommand>>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let members: Vec<Member> = from_value(Value::Array(
+        pre_guild.unwrap().get::<&str, Vec<Value>>("members"),
+    ))
+    .unwrap();
+
+    // Commands of every bot in the guild
+    let commands = database
+        .query(
+            "SELECT * FROM commands WHERE application = any($1) AND (guild IS NULL OR guild = $2) ORDER BY name",
+            &[
+                &members
+                    .into_iter()
+                    .map(|member| member.id)
+                    .collect::<Vec<String>>(),
+                &Uuid::parse_str(guild_id).unwrap(),
+            ],
+        )
+        .await?;
+
+    Ok(Json(commands.iter().map(get_returned_command).collect()))
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![
+        get_global_commands,
+        create_global_command,
+        get_guild_commands,
+        create_guild_command,
+        del_command,
+        get_available_commands
+    ]
+}
@@ -0,0 +1,180 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::structs::{DiscoverableGuild, Member, ReturnedGuild};
+use crate::{AppError, Auth};
+
+use rocket::{
+    http::Status,
+    serde::json::{serde_json, to_value, Json, Value},
+    Route, State,
+};
+use uuid::Uuid;
+
+// Maximum length of a search query
+const MAX_QUERY_LENGTH: usize = 100;
+// Amount of guilds returned by default, and at most
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 100;
+
+#[get("/discovery/guilds?<q>&<sort>&<after>&<limit>", format = "json")]
+async fn get_discoverable_guilds(
+    q: Option<&str>,
+    sort: Option<&str>,
+    after: Option<&str>,
+    limit: Option<i64>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<DiscoverableGuild>>, AppError> {
+    let q = q.map(|q| q.trim()).filter(|q| !q.is_empty());
+
+    // Check if query is too long
+    if q.is_some_and(|q| q.len() > MAX_QUERY_LENGTH) {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Check if sort is valid, relevance needs a query
+    let sort = sort.unwrap_or(if q.is_some() { "relevance" } else { "members" });
+
+    if !["members", "newest", "relevance"].contains(&sort) || (sort == "relevance" && q.is_none()) {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Keyset pagination over (key, id), where key depends on sort
+    let guilds = database
+        .query(
+            "WITH listed AS (
+                SELECT *, CASE $2
+                    WHEN 'newest' THEN creation::float8
+                    WHEN 'relevance' THEN ts_rank(
+                        to_tsvector('simple', name || ' ' || coalesce(description, '')),
+                        websearch_to_tsquery('simple', $1)
+                    )::float8
+                    ELSE coalesce(cardinality(members), 0)::float8
+                END AS key
+                FROM guilds WHERE public AND (
+                    $1::text IS NULL
+                    OR to_tsvector('simple', name || ' ' || coalesce(description, ''))
+                        @@ websearch_to_tsquery('simple', $1)
+                ) AND NOT EXISTS (
+                    SELECT 1
+                    FROM unnest(bans) AS id
+                    WHERE id = $3
+                )
+            )
+            SELECT * FROM listed WHERE $4::uuid IS NULL OR (key, id) < (
+                SELECT key, id FROM listed WHERE id = $4
+            ) ORDER BY key DESC, id DESC LIMIT $5",
+            &[
+                &q,
+                &sort,
+                &to_value(&user_id.0).unwrap(),
+                &after.and_then(|after| Uuid::parse_str(after).ok()),
+                &limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+            ],
+        )
+        .await?;
+
+    Ok(Json(
+        guilds
+            .iter()
+            .map(|guild| DiscoverableGuild {
+                id: guild.get::<&str, Uuid>("id").to_string(),
+                name: guild.get::<&str, String>("name"),
+                description: guild
+                    .try_get::<&str, Option<String>>("description")
+                    .unwrap_or(None),
+                icon: guild
+                    .try_get::<&str, Option<String>>("icon")
+                    .unwrap_or(None),
+                members: guild.get::<&str, Vec<Value>>("members").len(),
+                creation: guild.get::<&str, i64>("creation"),
+            })
+            .collect(),
+    ))
+}
+
+#[put("/discovery/guilds/<guild_id>", format = "json")]
+async fn join_discoverable_guild(
+    guild_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedGuild>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND public AND NOT EXISTS (
+               SELECT 1
+               FROM unnest(bans) AS id
+               WHERE id = $2
+           ) AND NOT EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $3
+           )",
+            &[
+                &Uuid::parse_str(guild_id).unwrap(),
+                &to_value(&user_id.0).unwrap(),
+                &user_id.0,
+            ],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Append member
+    database
+        .execute(
+            "UPDATE guilds SET members = array_append(members, $1) WHERE id = $2",
+            &[
+                &to_value(Member {
+                    id: user_id.0,
+                    nickname: None,
+                    roles: vec!["11111111-1111-1111-1111-111111111111".to_string()],
+                })
+                .unwrap(),
+                &guild.get::<&str, Uuid>("id"),
+            ],
+        )
+        .await?;
+
+    Ok(Json(ReturnedGuild {
+        id: guild.get::<&str, Uuid>("id").to_string(),
+        name: guild.get::<&str, String>("name"),
+        description: guild
+            .try_get::<&str, Option<String>>("description")
+            .unwrap_or(None),
+        icon: guild
+            .try_get::<&str, Option<String>>("icon")
+            .unwrap_or(None),
+        public: guild.get::<&str, bool>("public"),
+        roles: serde_json::from_value(Value::Array(guild.get::<&str, Vec<Value>>("roles")))
+            .unwrap(),
+        members: guild.get::<&str, Vec<Value>>("members").len() + 1,
+        creation: guild.get::<&str, i64>("creation"),
+    }))
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![get_discoverable_guilds, join_discoverable_guild]
+}
@@ -0,0 +1,686 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::structs::{
+    CreateDMBody, CreateMessageBody, EditMessageBody, Message, ReadState, ReturnedDM, ReturnedUser,
+};
+use crate::{
+    routes::relationships::is_blocked,
+    utils::{
+        self, messages::Destination, storage::StorageBackend, subscriptions::Dispatcher,
+        unfurl::Unfurler,
+    },
+    AppError, Auth,
+};
+
+use rocket::{http::Status, serde::json::Json, Route, State};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+// Maximum amount of users in a group DM
+const MAX_GROUP_RECIPIENTS: usize = 10;
+
+async fn get_returned_dm(
+    database: &tokio_postgres::Client,
+    dm: &Row,
+) -> Result<ReturnedDM, tokio_postgres::Error> {
+    let recipients = database
+        .query(
+            "SELECT * FROM users WHERE id::text = any($1)",
+            &[&dm.get::<&str, Vec<String>>("recipients")],
+        )
+        .await?;
+
+    Ok(ReturnedDM {
+        id: dm.get::<&str, Uuid>("id").to_string(),
+        r#type: dm.get::<&str, String>("type"),
+        name: dm.try_get::<&str, Option<String>>("name").unwrap_or(None),
+        owner: dm.try_get::<&str, Option<String>>("owner").unwrap_or(None),
+        recipients: recipients
+            .iter()
+            .map(|user| ReturnedUser {
+                id: user.get::<&str, Uuid>("id").to_string(),
+                username: user.get::<&str, String>("username"),
+                discriminator: user.get::<&str, String>("discriminator"),
+                avatar: user
+                    .try_get::<&str, Option<String>>("avatar")
+                    .unwrap_or(None),
+                about: user
+                    .try_get::<&str, Option<String>>("about")
+                    .unwrap_or(None),
+                creation: user.get::<&str, i64>("creation"),
+                bot: user.get::<&str, String>("type") == "BOT",
+            })
+            .collect(),
+        creation: dm.get::<&str, i64>("creation"),
+    })
+}
+
+// Get the recipients of a DM the user can send messages in
+pub async fn get_sendable_dm_recipients(
+    database: &tokio_postgres::Client,
+    channel_id: &str,
+    user_id: &str,
+) -> Result<Vec<String>, AppError> {
+    // Get DM
+    let pre_dm = database
+        .query_one(
+            "SELECT * FROM dms WHERE id = $1 AND $2 = any(recipients)",
+            &[&Uuid::parse_str(channel_id).unwrap(), &user_id],
+        )
+        .await;
+
+    if pre_dm.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let dm = pre_dm.unwrap();
+    let recipients = dm.get::<&str, Vec<String>>("recipients");
+
+    // Check if either user of a 1:1 DM blocked the other
+    if dm.get::<&str, String>("type") == "dm" {
+        let other = recipients
+            .iter()
+            .find(|recipient| *recipient != user_id)
+            .unwrap();
+
+        if is_blocked(database, other, user_id).await?
+            || is_blocked(database, user_id, other).await?
+        {
+            return Err(AppError(Status::Forbidden));
+        }
+    }
+
+    Ok(recipients)
+}
+
+#[get("/users/@me/channels", format = "json")]
+async fn get_my_dms(
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<ReturnedDM>>, AppError> {
+    // Get DMs
+    let dms = database
+        .query(
+            "SELECT * FROM dms WHERE $1 = any(recipients)",
+            &[&user_id.0],
+        )
+        .await?;
+
+    // Parse DMs
+    let mut returned_dms: Vec<ReturnedDM> = Vec::new();
+    for dm in dms.iter() {
+        returned_dms.push(get_returned_dm(database, dm).await?);
+    }
+
+    Ok(Json(returned_dms))
+}
+
+#[post("/users/@me/channels", format = "json", data = "<body>")]
+async fn create_dm(
+    body: Json<CreateDMBody>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedDM>, AppError> {
+    // Get unique recipients, including the current user
+    let mut recipients = vec![user_id.0.clone()];
+    for recipient in body.recipients.iter() {
+        if !recipients.contains(recipient) {
+            recipients.push(recipient.to_string());
+        }
+    }
+
+    // Check if there are too few or too many recipients, or if name is too long
+    if recipients.len() < 2
+        || recipients.len() > MAX_GROUP_RECIPIENTS
+        || (body.name.is_some() && body.name.as_ref().unwrap().len() > 30)
+    {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Check if every recipient exists
+    if recipients
+        .iter()
+        .any(|recipient| Uuid::parse_str(recipient).is_err())
+        || database
+            .query(
+                "SELECT * FROM users WHERE id::text = any($1)",
+                &[&recipients],
+            )
+            .await?
+            .len()
+            != recipients.len()
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if any recipient blocked the current user
+    for recipient in recipients.iter() {
+        if is_blocked(database, recipient, &user_id.0).await? {
+            return Err(AppError(Status::Forbidden));
+        }
+    }
+
+    let r#type = if recipients.len() == 2 { "dm" } else { "group" };
+
+    // Reuse the existing DM between both users
+    if r#type == "dm" {
+        let existing_dm = database
+            .query_opt(
+                "SELECT * FROM dms WHERE type = 'dm' AND recipients @> $1",
+                &[&recipients],
+            )
+            .await?;
+
+        if let Some(existing_dm) = existing_dm {
+            return Ok(Json(get_returned_dm(database, &existing_dm).await?));
+        }
+    }
+
+    // Create DM
+    let dm = database
+        .query_one(
+            "INSERT INTO dms (id, type, name, owner, recipients, creation) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+            &[
+                &Uuid::new_v4(),
+                &r#type,
+                &if r#type == "group" { body.name.clone() } else { None },
+                &if r#type == "group" { Some(user_id.0.clone()) } else { None },
+                &recipients,
+                &(SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs() as i64),
+            ],
+        )
+        .await?;
+
+    let returned_dm = get_returned_dm(database, &dm).await?;
+
+    // Broadcast dmCreated event to every recipient
+    for recipient in recipients {
+        utils::sse::broadcast(
+            sse_clients,
+            &recipient,
+            utils::structs::SSEEvent::DmCreated {
+                channel: &returned_dm,
+            },
+        )
+        .await;
+    }
+
+    Ok(Json(returned_dm))
+}
+
+#[put("/channels/<channel_id>/recipients/<recipient_id>", format = "json")]
+async fn add_dm_recipient(
+    channel_id: &str,
+    recipient_id: &str,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedDM>, AppError> {
+    // Get group DM
+    let pre_dm = database
+        .query_one(
+            "SELECT * FROM dms WHERE id = $1 AND type = 'group' AND $2 = any(recipients)",
+            &[&Uuid::parse_str(channel_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_dm.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let recipients = pre_dm.unwrap().get::<&str, Vec<String>>("recipients");
+
+    // Check if already a recipient
+    if recipients.contains(&recipient_id.to_string()) {
+        return Err(AppError(Status::Conflict));
+    }
+
+    // Check if the group is full
+    if recipients.len() >= MAX_GROUP_RECIPIENTS {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Check if the new recipient exists
+    if database
+        .query_one(
+            "SELECT * FROM users WHERE id = $1",
+            &[&Uuid::parse_str(recipient_id).unwrap()],
+        )
+        .await
+        .is_err()
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if the new recipient blocked the current user
+    if is_blocked(database, recipient_id, &user_id.0).await? {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Append recipient
+    let dm = database
+        .query_one(
+            "UPDATE dms SET recipients = array_append(recipients, $1) WHERE id = $2 RETURNING *",
+            &[&recipient_id, &Uuid::parse_str(channel_id).unwrap()],
+        )
+        .await?;
+
+    let returned_dm = get_returned_dm(database, &dm).await?;
+
+    // Broadcast dmEdited event to every previous recipient
+    for recipient in recipients {
+        utils::sse::broadcast(
+            sse_clients,
+            &recipient,
+            utils::structs::SSEEvent::DmEdited {
+                channel: &returned_dm,
+            },
+        )
+        .await;
+    }
+
+    // Broadcast dmCreated event to the new recipient
+    utils::sse::broadcast(
+        sse_clients,
+        recipient_id,
+        utils::structs::SSEEvent::DmCreated {
+            channel: &returned_dm,
+        },
+    )
+    .await;
+
+    Ok(Json(returned_dm))
+}
+
+#[delete("/channels/<channel_id>/recipients/<recipient_id>", format = "json")]
+async fn del_dm_recipient(
+    channel_id: &str,
+    recipient_id: &str,
+    storage: &State<StorageBackend>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    // Get group DM
+    let pre_dm = database
+        .query_one(
+            "SELECT * FROM dms WHERE id = $1 AND type = 'group' AND $2 = any(recipients)",
+            &[&Uuid::parse_str(channel_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_dm.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let dm = pre_dm.unwrap();
+    let mut recipients = dm.get::<&str, Vec<String>>("recipients");
+    let owner = dm.try_get::<&str, Option<String>>("owner").unwrap_or(None);
+
+    // Check if a recipient
+    if !recipients.contains(&recipient_id.to_string()) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Only the owner can remove others
+    if recipient_id != user_id.0 && owner.as_ref() != Some(&user_id.0) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    recipients.retain(|recipient| recipient != recipient_id);
+
+    if recipients.is_empty() {
+        // Delete the empty group
+        utils::messages::purge_messages(database, storage, Some(channel_id), None).await?;
+        database
+            .execute(
+                "DELETE FROM dms WHERE id = $1",
+                &[&Uuid::parse_str(channel_id).unwrap()],
+            )
+            .await?;
+    } else {
+        // Transfer ownership if the owner left
+        let new_owner = if owner.as_deref() == Some(recipient_id) {
+            Some(recipients[0].clone())
+        } else {
+            owner
+        };
+
+        let dm = database
+            .query_one(
+                "UPDATE dms SET recipients = $1, owner = $2 WHERE id = $3 RETURNING *",
+                &[
+                    &recipients,
+                    &new_owner,
+                    &Uuid::parse_str(channel_id).unwrap(),
+                ],
+            )
+            .await?;
+
+        let returned_dm = get_returned_dm(database, &dm).await?;
+
+        // Broadcast dmEdited event to every remaining recipient
+        for recipient in recipients {
+            utils::sse::broadcast(
+                sse_clients,
+                &recipient,
+                utils::structs::SSEEvent::DmEdited {
+                    channel: &returned_dm,
+                },
+            )
+            .await;
+        }
+    }
+
+    // Broadcast dmLeft event to the removed recipient
+    utils::sse::broadcast(
+        sse_clients,
+        recipient_id,
+        utils::structs::SSEEvent::DmLeft { channel_id },
+    )
+    .await;
+
+    Ok(Json(HashMap::new()))
+}
+
+#[get("/channels/<channel_id>/messages?<before>&<limit>", format = "json")]
+async fn get_dm_messages(
+    channel_id: &str,
+    before: Option<&str>,
+    limit: Option<i64>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<Message>>, AppError> {
+    // Check if a recipient of the DM
+    if database
+        .query_one(
+            "SELECT * FROM dms WHERE id = $1 AND $2 = any(recipients)",
+            &[&Uuid::parse_str(channel_id).unwrap(), &user_id.0],
+        )
+        .await
+        .is_err()
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    Ok(Json(
+        utils::messages::get_messages(database, &user_id.0, channel_id, before, limit).await?,
+    ))
+}
+
+#[post("/channels/<channel_id>/messages", format = "json", data = "<body>")]
+async fn create_dm_message(
+    channel_id: &str,
+    body: Json<CreateMessageBody>,
+    dispatcher: &State<Dispatcher>,
+    unfurler: &State<Unfurler>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Message>, AppError> {
+    // Check if content is empty or too long
+    if body.content.trim().is_empty() || body.content.len() > utils::messages::MAX_CONTENT_LENGTH {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    let recipients = get_sendable_dm_recipients(database, channel_id, &user_id.0).await?;
+
+    let mut message = utils::messages::resolve_dm_mentions(
+        &recipients,
+        utils::messages::new_message(&user_id.0, &body.content, "default"),
+    );
+
+    if let Some(reply_to) = &body.reply_to {
+        message = utils::messages::reply(
+            database,
+            channel_id,
+            reply_to,
+            body.mention_author.unwrap_or(true),
+            message,
+        )
+        .await?;
+    }
+
+    let message = utils::messages::create_message(database, channel_id, None, message).await?;
+
+    utils::messages::send_message(
+        database,
+        sse_clients,
+        dispatcher,
+        unfurler,
+        Destination::Dm(&recipients),
+        channel_id,
+        &message,
+    )
+    .await?;
+
+    Ok(Json(message))
+}
+
+#[post("/channels/<channel_id>/messages/<message_id>/ack", format = "json")]
+async fn ack_dm_message(
+    channel_id: &str,
+    message_id: &str,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReadState>, AppError> {
+    // Check if a recipient of the DM
+    if database
+        .query_one(
+            "SELECT * FROM dms WHERE id = $1 AND $2 = any(recipients)",
+            &[&Uuid::parse_str(channel_id).unwrap(), &user_id.0],
+        )
+        .await
+        .is_err()
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Get message
+    let pre_message = database
+        .query_one(
+            "SELECT * FROM messages WHERE id = $1 AND channel = $2 AND deleted IS NULL",
+            &[
+                &Uuid::parse_str(message_id).unwrap(),
+                &Uuid::parse_str(channel_id).unwrap(),
+            ],
+        )
+        .await;
+
+    if pre_message.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let message = utils::messages::parse_message(&pre_message.unwrap());
+
+    let read_state =
+        utils::read_states::ack(database, &user_id.0, channel_id, None, &vec![], &message).await?;
+
+    // Broadcast channelAcked event to every session of the user
+    utils::sse::broadcast(
+        sse_clients,
+        &user_id.0,
+        utils::structs::SSEEvent::ChannelAcked {
+            guild_id: None,
+            read_state: &read_state,
+        },
+    )
+    .await;
+
+    Ok(Json(read_state))
+}
+
+#[patch(
+    "/channels/<channel_id>/messages/<message_id>",
+    format = "json",
+    data = "<body>"
+)]
+async fn edit_dm_message(
+    channel_id: &str,
+    message_id: &str,
+    body: Json<EditMessageBody>,
+    unfurler: &State<Unfurler>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Message>, AppError> {
+    // Check if content is empty or too long
+    if body.content.trim().is_empty() || body.content.len() > utils::messages::MAX_CONTENT_LENGTH {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    let recipients = get_sendable_dm_recipients(database, channel_id, &user_id.0).await?;
+
+    // Get message
+    let pre_message = database
+        .query_one(
+            "SELECT * FROM messages WHERE id = $1 AND channel = $2 AND deleted IS NULL",
+            &[
+                &Uuid::parse_str(message_id).unwrap(),
+                &Uuid::parse_str(channel_id).unwrap(),
+            ],
+        )
+        .await;
+
+    if pre_message.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let row = pre_message.unwrap();
+    let previous = utils::messages::parse_message(&row);
+
+    // Only the author can edit their own messages
+    if previous.author != user_id.0 || previous.r#type != "default" {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    let message = utils::messages::resolve_dm_mentions(
+        &recipients,
+        utils::messages::edited_message(&row, &body.content),
+    );
+
+    let message = utils::messages::edit_message(database, None, &previous, message).await?;
+
+    // Broadcast messageUpdated event to every recipient
+    for recipient in recipients.iter() {
+        utils::sse::broadcast(
+            sse_clients,
+            recipient,
+            utils::structs::SSEEvent::MessageUpdated {
+                guild_id: None,
+                channel_id,
+                message: &message,
+            },
+        )
+        .await;
+    }
+
+    utils::unfurl::queue(unfurler, None, channel_id, &message, recipients);
+
+    Ok(Json(
+        utils::reactions::attach_reactions(database, &user_id.0, vec![message])
+            .await?
+            .remove(0),
+    ))
+}
+
+#[delete("/channels/<channel_id>/messages/<message_id>", format = "json")]
+async fn delete_dm_message(
+    channel_id: &str,
+    message_id: &str,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    // Get DM
+    let pre_dm = database
+        .query_one(
+            "SELECT * FROM dms WHERE id = $1 AND $2 = any(recipients)",
+            &[&Uuid::parse_str(channel_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_dm.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let recipients = pre_dm.unwrap().get::<&str, Vec<String>>("recipients");
+
+    // Get message
+    let pre_message = database
+        .query_one(
+            "SELECT * FROM messages WHERE id = $1 AND channel = $2 AND deleted IS NULL",
+            &[
+                &Uuid::parse_str(message_id).unwrap(),
+                &Uuid::parse_str(channel_id).unwrap(),
+            ],
+        )
+        .await;
+
+    if pre_message.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Only the author can delete their own messages
+    if pre_message.unwrap().get::<&str, String>("author") != user_id.0 {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    utils::messages::delete_message(database, message_id, &user_id.0).await?;
+
+    // Broadcast messageDeleted event to every recipient
+    for recipient in recipients.iter() {
+        utils::sse::broadcast(
+            sse_clients,
+            recipient,
+            utils::structs::SSEEvent::MessageDeleted {
+                guild_id: None,
+                channel_id,
+                message_id,
+            },
+        )
+        .await;
+    }
+
+    Ok(Json(HashMap::new()))
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![
+        get_my_dms,
+        create_dm,
+        add_dm_recipient,
+        del_dm_recipient,
+        get_dm_messages,
+        create_dm_message,
+        ack_dm_message,
+        edit_dm_message,
+        delete_dm_message
+    ]
+}
@@ -0,0 +1,345 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::structs::{Member, PatchEmojiBody, ReturnedEmoji};
+use crate::{
+    utils::{
+        self,
+        permissions::{check_guild_permission, GuildPermissions},
+        storage::StorageBackend,
+    },
+    AppError, Auth,
+};
+
+use rocket::{
+    data::{Data, ToByteUnit},
+    http::Status,
+    serde::json::{from_value, Json, Value},
+    Route, State,
+};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+// Maximum size of an uploaded emoji, in bytes
+const MAX_EMOJI_SIZE: u64 = 1024 * 1024;
+// Maximum amount of emojis per guild
+const MAX_EMOJIS: i64 = 50;
+
+// Check if the name only has letters, digits and underscores, and is 2 to 32 long
+fn is_valid_name(name: &str) -> bool {
+    (2..=32).contains(&name.len())
+        && name
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '_')
+}
+
+fn returned_emoji(emoji: &Row) -> ReturnedEmoji {
+    ReturnedEmoji {
+        id: emoji.get::<&str, Uuid>("id").to_string(),
+        name: emoji.get::<&str, String>("name"),
+        image: emoji.get::<&str, String>("image"),
+        author: emoji.get::<&str, String>("author"),
+        creation: emoji.get::<&str, i64>("creation"),
+    }
+}
+
+// Broadcast guildEmojisUpdated event to every member
+async fn broadcast_emojis(
+    database: &tokio_postgres::Client,
+    sse_clients: &crate::SSEClients,
+    guild: &Row,
+) -> Result<(), AppError> {
+    let emojis: Vec<ReturnedEmoji> = database
+        .query(
+            "SELECT * FROM emojis WHERE guild = $1 ORDER BY creation",
+            &[&guild.get::<&str, Uuid>("id")],
+        )
+        .await?
+        .iter()
+        .map(returned_emoji)
+        .collect();
+
+    let guild_id = guild.get::<&str, Uuid>("id").to_string();
+    let members: Vec<Member> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("members"))).unwrap();
+
+    for member in members {
+        utils::sse::broadcast(
+            sse_clients,
+            &member.id,
+            utils::structs::SSEEvent::GuildEmojisUpdated {
+                guild_id: &guild_id,
+                emojis: &emojis,
+            },
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+#[get("/guilds/<guild_id>/emojis", format = "json")]
+async fn get_emojis(
+    guild_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<ReturnedEmoji>>, AppError> {
+    // Check if a member of the guild
+    if database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await
+        .is_err()
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let emojis = database
+        .query(
+            "SELECT * FROM emojis WHERE guild = $1 ORDER BY creation",
+            &[&Uuid::parse_str(guild_id).unwrap()],
+        )
+        .await?;
+
+    Ok(Json(emojis.iter().map(returned_emoji).collect()))
+}
+
+#[post("/guilds/<guild_id>/emojis?<name>", data = "<body>")]
+async fn create_emoji(
+    guild_id: &str,
+    name: &str,
+    body: Data<'_>,
+    storage: &State<StorageBackend>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedEmoji>, AppError> {
+    // Check if name is valid
+    if !is_valid_name(name) {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if can manage emojis
+    if !check_guild_permission(&guild, &user_id.0, GuildPermissions::MANAGE_EMOJIS) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Check if the guild has too many emojis, or one with the same name
+    let existing = database
+        .query_one(
+            "SELECT count(*) AS count, bool_or(name = $2) AS taken FROM emojis WHERE guild = $1",
+            &[&Uuid::parse_str(guild_id).unwrap(), &name],
+        )
+        .await?;
+
+    if existing.get::<&str, i64>("count") >= MAX_EMOJIS {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    if existing.get::<&str, Option<bool>>("taken").unwrap_or(false) {
+        return Err(AppError(Status::Conflict));
+    }
+
+    let data = body.open(MAX_EMOJI_SIZE.bytes()).into_bytes().await?;
+
+    // Check if image is too large
+    if !data.is_complete() {
+        return Err(AppError(Status::PayloadTooLarge));
+    }
+
+    let image = utils::images::store_image(storage, "emojis", data.into_inner()).await?;
+
+    let emoji = database.query_one("INSERT INTO emojis (id, guild, name, image, author, creation) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+    &[
+        &Uuid::new_v4(),
+        &Uuid::parse_str(guild_id).unwrap(),
+        &name,
+        &image,
+        &user_id.0,
+        &(SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64),
+    ]).await?;
+
+    broadcast_emojis(database, sse_clients, &guild).await?;
+
+    Ok(Json(returned_emoji(&emoji)))
+}
+
+#[patch(
+    "/guilds/<guild_id>/emojis/<emoji_id>",
+    format = "json",
+    data = "<body>"
+)]
+async fn patch_emoji(
+    guild_id: &str,
+    emoji_id: &str,
+    body: Json<PatchEmojiBody>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedEmoji>, AppError> {
+    // Check if name is valid
+    if !is_valid_name(&body.name) {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if can manage emojis
+    if !check_guild_permission(&guild, &user_id.0, GuildPermissions::MANAGE_EMOJIS) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Check if another emoji has the name
+    if database
+        .query_one(
+            "SELECT * FROM emojis WHERE guild = $1 AND name = $2 AND id <> $3",
+            &[
+                &Uuid::parse_str(guild_id).unwrap(),
+                &body.name,
+                &Uuid::parse_str(emoji_id).unwrap(),
+            ],
+        )
+        .await
+        .is_ok()
+    {
+        return Err(AppError(Status::Conflict));
+    }
+
+    // Rename emoji
+    let pre_emoji = database
+        .query_one(
+            "UPDATE emojis SET name = $1 WHERE id = $2 AND guild = $3 RETURNING *",
+            &[
+                &body.name,
+                &Uuid::parse_str(emoji_id).unwrap(),
+                &Uuid::parse_str(guild_id).unwrap(),
+            ],
+        )
+        .await;
+
+    if pre_emoji.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    broadcast_emojis(database, sse_clients, &guild).await?;
+
+    Ok(Json(returned_emoji(&pre_emoji.unwrap())))
+}
+
+#[delete("/guilds/<guild_id>/emojis/<emoji_id>", format = "json")]
+async fn del_emoji(
+    guild_id: &str,
+    emoji_id: &str,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if can manage emojis
+    if !check_guild_permission(&guild, &user_id.0, GuildPermissions::MANAGE_EMOJIS) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Delete emoji
+    if database
+        .execute(
+            "DELETE FROM emojis WHERE id = $1 AND guild = $2",
+            &[
+                &Uuid::parse_str(emoji_id).unwrap(),
+                &Uuid::parse_str(guild_id).unwrap(),
+            ],
+        )
+        .await?
+        < 1
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    broadcast_emojis(database, sse_clients, &guild).await?;
+
+    Ok(Json(HashMap::new()))
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![get_emojis, create_emoji, patch_emoji, del_emoji]
+}
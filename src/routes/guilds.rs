@@ -24,12 +24,16 @@ use crate::{
     to_json_array,
     utils::{
         self,
+        images::MAX_IMAGE_SIZE,
         permissions::{check_guild_permission, ChannelPermissions, GuildPermissions},
+        storage::StorageBackend,
+        subscriptions::Dispatcher,
     },
     AppError, Auth,
 };
 
 use rocket::{
+    data::{Data, ToByteUnit},
     http::Status,
     serde::json::{from_value, serde_json, to_value, Json, Value},
     Route, State,
@@ -43,7 +47,7 @@ use uuid::Uuid;
 #[get("/guilds/<guild_id>", format = "json")]
 async fn get_guild(
     guild_id: &str,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
 ) -> Result<Json<ReturnedGuild>, AppError> {
     // Get guild
@@ -85,7 +89,7 @@ async fn get_guild(
 async fn create_guild(
     body: Json<CreateGuildBody>,
     sse_clients: &State<crate::SSEClients>,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
 ) -> Result<Json<ReturnedGuild>, AppError> {
     // Check if name is too long
@@ -123,6 +127,8 @@ async fn create_guild(
             ],
             messages: vec![],
             pins: vec![],
+            parent: None,
+            thread: None,
         }],
         roles: vec![
             Role {
@@ -187,10 +193,8 @@ async fn create_guild(
     utils::sse::broadcast(
         sse_clients,
         &user_id.0,
-        utils::structs::SSEEvent {
-            event: "guildJoined",
-            guild: Some(&returned_guild),
-            ..Default::default()
+        utils::structs::SSEEvent::GuildJoined {
+            guild: &returned_guild,
         },
     )
     .await;
@@ -202,8 +206,9 @@ async fn create_guild(
 async fn update_guild(
     guild_id: &str,
     body: Json<PatchGuildBody>,
+    dispatcher: &State<Dispatcher>,
     sse_clients: &State<crate::SSEClients>,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
 ) -> Result<Json<ReturnedGuild>, AppError> {
     // Check if name or description are too long
@@ -343,15 +348,112 @@ async fn update_guild(
         utils::sse::broadcast(
             sse_clients,
             &member.id,
-            utils::structs::SSEEvent {
-                event: "guildEdited",
-                guild: Some(&final_guild),
-                ..Default::default()
+            utils::structs::SSEEvent::GuildEdited {
+                guild: &final_guild,
             },
         )
         .await;
     }
 
+    // Dispatch guildEdited event to the guild's subscriptions
+    utils::subscriptions::dispatch(
+        dispatcher,
+        guild_id,
+        utils::structs::SSEEvent::GuildEdited {
+            guild: &final_guild,
+        },
+    );
+
+    Ok(Json(final_guild))
+}
+
+#[put("/guilds/<guild_id>/icon", data = "<body>")]
+async fn put_guild_icon(
+    guild_id: &str,
+    body: Data<'_>,
+    storage: &State<StorageBackend>,
+    dispatcher: &State<Dispatcher>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedGuild>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if can manage the guild
+    if !check_guild_permission(&guild, &user_id.0, GuildPermissions::MANAGE_GUILD) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    let data = body.open(MAX_IMAGE_SIZE.bytes()).into_bytes().await?;
+
+    // Check if image is too large
+    if !data.is_complete() {
+        return Err(AppError(Status::PayloadTooLarge));
+    }
+
+    let icon = utils::images::store_image(storage, "icons", data.into_inner()).await?;
+
+    database
+        .execute(
+            "UPDATE guilds SET icon = $1 WHERE id = $2",
+            &[&icon, &Uuid::parse_str(guild_id).unwrap()],
+        )
+        .await?;
+
+    let final_guild = ReturnedGuild {
+        id: guild.get::<&str, Uuid>("id").to_string(),
+        name: guild.get::<&str, String>("name"),
+        description: guild
+            .try_get::<&str, Option<String>>("description")
+            .unwrap_or(None),
+        icon: Some(icon),
+        public: guild.get::<&str, bool>("public"),
+        roles: serde_json::from_value(Value::Array(guild.get::<&str, Vec<Value>>("roles")))
+            .unwrap(),
+        members: guild.get::<&str, Vec<Value>>("members").len(),
+        creation: guild.get::<&str, i64>("creation"),
+    };
+
+    let members: Vec<Member> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("members"))).unwrap();
+
+    // Broadcast guildEdited event to every member
+    for member in members {
+        utils::sse::broadcast(
+            sse_clients,
+            &member.id,
+            utils::structs::SSEEvent::GuildEdited {
+                guild: &final_guild,
+            },
+        )
+        .await;
+    }
+
+    // Dispatch guildEdited event to the guild's subscriptions
+    utils::subscriptions::dispatch(
+        dispatcher,
+        guild_id,
+        utils::structs::SSEEvent::GuildEdited {
+            guild: &final_guild,
+        },
+    );
+
     Ok(Json(final_guild))
 }
 
@@ -359,7 +461,7 @@ async fn update_guild(
 async fn del_guild(
     guild_id: &str,
     sse_clients: &State<crate::SSEClients>,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
 ) -> Result<Json<HashMap<String, String>>, AppError> {
     // Get guild
@@ -417,11 +519,7 @@ async fn del_guild(
         utils::sse::broadcast(
             sse_clients,
             &member.id,
-            utils::structs::SSEEvent {
-                event: "guildLeft",
-                guild_id: Some(guild_id),
-                ..Default::default()
-            },
+            utils::structs::SSEEvent::GuildLeft { guild_id },
         )
         .await;
     }
@@ -432,7 +530,7 @@ async fn del_guild(
 #[get("/guilds/<guild_id>/bans", format = "json")]
 async fn get_guild_bans(
     guild_id: &str,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
 ) -> Result<Json<Vec<ReturnedUser>>, AppError> {
     // Get guild
@@ -482,6 +580,7 @@ async fn get_guild_bans(
                     .try_get::<&str, Option<String>>("about")
                     .unwrap_or(None),
                 creation: user.get::<&str, i64>("creation"),
+                bot: user.get::<&str, String>("type") == "BOT",
             })
             .collect(),
     ))
@@ -491,8 +590,9 @@ async fn get_guild_bans(
 async fn del_guild_ban(
     guild_id: &str,
     banned_id: &str,
+    dispatcher: &State<Dispatcher>,
     sse_clients: &State<crate::SSEClients>,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
 ) -> Result<Json<ReturnedUser>, AppError> {
     // Get guild
@@ -547,6 +647,7 @@ async fn del_guild_ban(
             .try_get::<&str, Option<String>>("about")
             .unwrap_or(None),
         creation: user.get::<&str, i64>("creation"),
+        bot: user.get::<&str, String>("type") == "BOT",
     };
 
     let members: Vec<Member> =
@@ -558,17 +659,25 @@ async fn del_guild_ban(
             utils::sse::broadcast(
                 sse_clients,
                 &member.id,
-                utils::structs::SSEEvent {
-                    event: "memberUnbanned",
-                    guild_id: Some(guild_id),
-                    member: Some(&returned_user),
-                    ..Default::default()
+                utils::structs::SSEEvent::MemberUnbanned {
+                    guild_id,
+                    member: &returned_user,
                 },
             )
             .await;
         }
     }
 
+    // Dispatch memberUnbanned event to the guild's subscriptions
+    utils::subscriptions::dispatch(
+        dispatcher,
+        guild_id,
+        utils::structs::SSEEvent::MemberUnbanned {
+            guild_id,
+            member: &returned_user,
+        },
+    );
+
     Ok(Json(returned_user))
 }
 
@@ -578,6 +687,7 @@ pub fn get_routes() -> Vec<Route> {
         get_guild,
         create_guild,
         update_guild,
+        put_guild_icon,
         del_guild,
         get_guild_bans,
         del_guild_ban
@@ -0,0 +1,80 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    utils::{images::IMAGE_SIZES, storage::StorageBackend},
+    AppError,
+};
+
+use rocket::{
+    http::{ContentType, Status},
+    Route, State,
+};
+
+// Get a stored image, falling back to the largest size
+async fn get_image(
+    storage: &StorageBackend,
+    prefix: &str,
+    hash: &str,
+    size: Option<u32>,
+) -> Result<(ContentType, Vec<u8>), AppError> {
+    let size = size.unwrap_or(IMAGE_SIZES[0]);
+
+    // Check if hash and size are valid
+    if !IMAGE_SIZES.contains(&size) || !hash.chars().all(|char| char.is_ascii_hexdigit()) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let data = storage
+        .get(&format!("{}/{}/{}.png", prefix, hash, size))
+        .await?;
+
+    Ok((ContentType::PNG, data))
+}
+
+#[get("/avatars/<hash>?<size>")]
+async fn get_avatar(
+    hash: &str,
+    size: Option<u32>,
+    storage: &State<StorageBackend>,
+) -> Result<(ContentType, Vec<u8>), AppError> {
+    get_image(storage, "avatars", hash, size).await
+}
+
+#[get("/icons/<hash>?<size>")]
+async fn get_icon(
+    hash: &str,
+    size: Option<u32>,
+    storage: &State<StorageBackend>,
+) -> Result<(ContentType, Vec<u8>), AppError> {
+    get_image(storage, "icons", hash, size).await
+}
+
+#[get("/emojis/<hash>?<size>")]
+async fn get_emoji(
+    hash: &str,
+    size: Option<u32>,
+    storage: &State<StorageBackend>,
+) -> Result<(ContentType, Vec<u8>), AppError> {
+    get_image(storage, "emojis", hash, size).await
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![get_avatar, get_icon, get_emoji]
+}
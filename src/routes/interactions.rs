@@ -0,0 +1,533 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::structs::{
+    Channel, CommandOption, CreateInteractionBody, CreateMessageBody, Interaction,
+    InteractionCallbackBody, InteractionOption, Member, Message,
+};
+use crate::{
+    utils::{
+        self,
+        messages::Destination,
+        permissions::{check_channel_permission, ChannelPermissions},
+        subscriptions::Dispatcher,
+        unfurl::Unfurler,
+    },
+    AppError, Auth,
+};
+
+use rand::Rng;
+use reqwest::{redirect::Policy, Client};
+use rocket::{
+    http::Status,
+    serde::json::{from_value, serde_json, Json, Value},
+    tokio, Route, State,
+};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+// Seconds an interaction can be responded to
+const INTERACTION_TIMEOUT: i64 = 15 * 60;
+// Seconds before an HTTP delivery is aborted
+const DELIVERY_TIMEOUT: u64 = 5;
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// Check if the value matches the option's type
+fn is_valid_value(r#type: &str, value: &Value) -> bool {
+    match r#type {
+        "string" => value
+            .as_str()
+            .is_some_and(|value| value.len() <= utils::messages::MAX_CONTENT_LENGTH),
+        "integer" => value.is_i64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "user" | "channel" => value
+            .as_str()
+            .is_some_and(|value| Uuid::parse_str(value).is_ok()),
+        _ => false,
+    }
+}
+
+// Check if every option is known, typed properly and given once, and none required is missing
+fn are_valid_options(command_options: &[CommandOption], options: &[InteractionOption]) -> bool {
+    options.iter().enumerate().all(|(i, option)| {
+        !options[..i].iter().any(|other| other.name == option.name)
+            && command_options
+                .iter()
+                .find(|command_option| command_option.name == option.name)
+                .is_some_and(|command_option| is_valid_value(&command_option.r#type, &option.value))
+    }) && command_options
+        .iter()
+        .filter(|command_option| command_option.required)
+        .all(|command_option| {
+            options
+                .iter()
+                .any(|option| option.name == command_option.name)
+        })
+}
+
+// Client shared by every delivery to the bots' URLs
+pub struct InteractionsClient(Client);
+
+pub fn client() -> InteractionsClient {
+    InteractionsClient(utils::network::public_client(
+        DELIVERY_TIMEOUT,
+        Policy::none(),
+        "FlyWayInteractions/1.0",
+    ))
+}
+
+// Send the interaction to the bot's URL, signed with its secret
+fn deliver_interaction(
+    client: &InteractionsClient,
+    url: String,
+    secret: String,
+    interaction: &Interaction,
+) {
+    // The URL is checked again, in case it was saved before being disallowed
+    if !utils::network::is_allowed_url(&url) {
+        return;
+    }
+
+    let payload = serde_json::to_string(interaction).unwrap();
+    let client = client.0.clone();
+
+    tokio::spawn(async move {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        let response = client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("X-FlyWay-Timestamp", timestamp.to_string())
+            .header(
+                "X-FlyWay-Signature",
+                format!(
+                    "sha256={}",
+                    utils::subscriptions::sign(&secret, timestamp, &payload)
+                ),
+            )
+            .body(payload)
+            .send()
+            .await;
+
+        if let Err(e) = response {
+            println!("{:}", e);
+        }
+    });
+}
+
+// Get an interaction that can still be responded to
+async fn get_interaction(
+    database: &tokio_postgres::Client,
+    interaction_id: &str,
+    token: &str,
+) -> Result<Row, AppError> {
+    let pre_interaction = database
+        .query_one(
+            "SELECT * FROM interactions WHERE id = $1 AND token = $2 AND creation > $3",
+            &[
+                &Uuid::parse_str(interaction_id).unwrap(),
+                &hash_token(token),
+                &(SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs() as i64
+                    - INTERACTION_TIMEOUT),
+            ],
+        )
+        .await;
+
+    if pre_interaction.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    Ok(pre_interaction.unwrap())
+}
+
+// Post a message from the bot into the interaction's channel
+async fn post_interaction_message(
+    database: &tokio_postgres::Client,
+    unfurler: &Unfurler,
+    dispatcher: &Dispatcher,
+    sse_clients: &crate::SSEClients,
+    interaction: &Row,
+    content: &str,
+) -> Result<Message, AppError> {
+    // Check if content is empty or too long
+    if content.trim().is_empty() || content.len() > utils::messages::MAX_CONTENT_LENGTH {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    let application_id = interaction.get::<&str, String>("application");
+    let guild_id = interaction.get::<&str, Uuid>("guild").to_string();
+    let channel_id = interaction.get::<&str, Uuid>("channel").to_string();
+
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&interaction.get::<&str, Uuid>("guild"), &application_id],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel still exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    if !channels.iter().any(|channel| channel.id == channel_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if the bot can send messages
+    if !check_channel_permission(
+        &guild,
+        &channel_id,
+        &application_id,
+        ChannelPermissions::SEND_MESSAGES,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    let message = utils::messages::create_message(
+        database,
+        &channel_id,
+        Some(&guild_id),
+        utils::messages::resolve_guild_mentions(
+            &guild,
+            check_channel_permission(
+                &guild,
+                &channel_id,
+                &application_id,
+                ChannelPermissions::MENTION_EVERYONE,
+            ),
+            utils::messages::new_message(&application_id, content, "interaction"),
+        ),
+    )
+    .await?;
+
+    utils::messages::send_message(
+        database,
+        sse_clients,
+        dispatcher,
+        unfurler,
+        Destination::Guild(&guild),
+        &channel_id,
+        &message,
+    )
+    .await?;
+
+    Ok(message)
+}
+
+#[post(
+    "/guilds/<guild_id>/channels/<channel_id>/interactions",
+    format = "json",
+    data = "<body>"
+)]
+async fn create_interaction(
+    guild_id: &str,
+    channel_id: &str,
+    body: Json<CreateInteractionBody>,
+    interactions_client: &State<InteractionsClient>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Interaction>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    if !channels.iter().any(|channel| channel.id == channel_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if can send messages
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::SEND_MESSAGES,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Get command
+    let pre_command = database
+        .query_one(
+            "SELECT * FROM commands WHERE id = $1 AND (guild IS NULL OR guild = $2)",
+            &[
+                &Uuid::parse_str(&body.command_id).unwrap(),
+                &Uuid::parse_str(guild_id).unwrap(),
+            ],
+        )
+        .await;
+
+    if pre_command.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let command = pre_command.unwrap();
+    let application_id = command.get::<&str, String>("application");
+
+    // Check if the bot is in the guild
+    let members: Vec<Member> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("members"))).unwrap();
+
+    if !members.iter().any(|member| member.id == application_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if options are valid
+    let command_options: Vec<CommandOption> =
+        from_value(Value::Array(command.get::<&str, Vec<Value>>("options"))).unwrap();
+
+    if !are_valid_options(&command_options, &body.options) {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Get bot
+    let bot = database
+        .query_one(
+            "SELECT * FROM users WHERE id = $1",
+            &[&Uuid::parse_str(&application_id).unwrap()],
+        )
+        .await?;
+    let interactions_url = bot
+        .try_get::<&str, Option<String>>("interactions_url")
+        .unwrap_or(None);
+
+    // Check if the bot can receive the interaction
+    if interactions_url.is_none()
+        && !sse_clients
+            .lock()
+            .await
+            .iter()
+            .any(|client| client.user == application_id)
+    {
+        return Err(AppError(Status::ServiceUnavailable));
+    }
+
+    // Create interaction, only the token's hash is kept
+    let token = hex::encode(rand::rng().random::<[u8; 32]>());
+    let interaction = Interaction {
+        id: Uuid::new_v4().to_string(),
+        application_id: application_id.clone(),
+        guild_id: guild_id.to_string(),
+        channel_id: channel_id.to_string(),
+        user_id: user_id.0.clone(),
+        command_id: body.command_id.clone(),
+        command_name: command.get::<&str, String>("name"),
+        options: body.options.clone(),
+        token: Some(token.clone()),
+        creation: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64,
+    };
+
+    database.execute("INSERT INTO interactions (id, application, guild, channel, invoker, command, token, state, creation) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+    &[
+        &Uuid::parse_str(&interaction.id).unwrap(),
+        &interaction.application_id,
+        &Uuid::parse_str(guild_id).unwrap(),
+        &Uuid::parse_str(channel_id).unwrap(),
+        &interaction.user_id,
+        &command.get::<&str, Uuid>("id"),
+        &hash_token(&token),
+        &"pending",
+        &interaction.creation,
+    ]).await?;
+
+    // Deliver the interaction to the bot's URL, or its event stream
+    match interactions_url {
+        Some(interactions_url) => deliver_interaction(
+            interactions_client,
+            interactions_url,
+            bot.get::<&str, String>("interactions_secret"),
+            &interaction,
+        ),
+        None => {
+            utils::sse::broadcast(
+                sse_clients,
+                &application_id,
+                utils::structs::SSEEvent::InteractionCreated {
+                    interaction: &interaction,
+                },
+            )
+            .await
+        }
+    }
+
+    // Only the bot gets the token
+    Ok(Json(Interaction {
+        token: None,
+        ..interaction
+    }))
+}
+
+#[post(
+    "/interactions/<interaction_id>/<token>/callback",
+    format = "json",
+    data = "<body>"
+)]
+async fn interaction_callback(
+    interaction_id: &str,
+    token: &str,
+    body: Json<InteractionCallbackBody>,
+    unfurler: &State<Unfurler>,
+    dispatcher: &State<Dispatcher>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+) -> Result<Json<Option<Message>>, AppError> {
+    let content = body.content.as_deref().unwrap_or("");
+
+    // Check if type is valid, and a message's content isn't empty or too long
+    if !["deferred", "message"].contains(&body.r#type.as_str())
+        || (body.r#type == "message"
+            && (content.trim().is_empty() || content.len() > utils::messages::MAX_CONTENT_LENGTH))
+    {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    let interaction = get_interaction(database, interaction_id, token).await?;
+
+    // Check if the interaction was already responded to
+    if database
+        .execute(
+            "UPDATE interactions SET state = $1 WHERE id = $2 AND state = 'pending'",
+            &[&body.r#type, &Uuid::parse_str(interaction_id).unwrap()],
+        )
+        .await?
+        < 1
+    {
+        return Err(AppError(Status::Conflict));
+    }
+
+    if body.r#type == "deferred" {
+        // Broadcast interactionDeferred event to the invoker
+        utils::sse::broadcast(
+            sse_clients,
+            &interaction.get::<&str, String>("invoker"),
+            utils::structs::SSEEvent::InteractionDeferred {
+                guild_id: &interaction.get::<&str, Uuid>("guild").to_string(),
+                channel_id: &interaction.get::<&str, Uuid>("channel").to_string(),
+                interaction_id,
+            },
+        )
+        .await;
+
+        return Ok(Json(None));
+    }
+
+    let message = post_interaction_message(
+        database,
+        unfurler,
+        dispatcher,
+        sse_clients,
+        &interaction,
+        content,
+    )
+    .await;
+
+    // Let the bot respond again if the message couldn't be posted
+    if message.is_err() {
+        database
+            .execute(
+                "UPDATE interactions SET state = 'pending' WHERE id = $1",
+                &[&Uuid::parse_str(interaction_id).unwrap()],
+            )
+            .await?;
+    }
+
+    Ok(Json(Some(message?)))
+}
+
+#[post(
+    "/interactions/<interaction_id>/<token>/followups",
+    format = "json",
+    data = "<body>"
+)]
+async fn create_followup(
+    interaction_id: &str,
+    token: &str,
+    body: Json<CreateMessageBody>,
+    unfurler: &State<Unfurler>,
+    dispatcher: &State<Dispatcher>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+) -> Result<Json<Message>, AppError> {
+    let interaction = get_interaction(database, interaction_id, token).await?;
+
+    // Check if the interaction was responded to first
+    if interaction.get::<&str, String>("state") == "pending" {
+        return Err(AppError(Status::Conflict));
+    }
+
+    Ok(Json(
+        post_interaction_message(
+            database,
+            unfurler,
+            dispatcher,
+            sse_clients,
+            &interaction,
+            &body.content,
+        )
+        .await?,
+    ))
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![create_interaction, interaction_callback, create_followup]
+}
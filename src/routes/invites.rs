@@ -37,7 +37,7 @@ use uuid::Uuid;
 #[get("/guilds/<guild_id>/invites", format = "json")]
 async fn get_guild_invites(
     guild_id: &str,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
 ) -> Result<Json<Vec<Invite>>, AppError> {
     // Get guild
@@ -73,7 +73,7 @@ async fn get_guild_invites(
 async fn create_guild_invite(
     body: Json<CreateInviteBody>,
     guild_id: &str,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
 ) -> Result<Json<Invite>, AppError> {
     // Get guild
@@ -130,7 +130,7 @@ async fn create_guild_invite(
 async fn delete_guild_invite(
     guild_id: &str,
     invite_code: &str,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
 ) -> Result<Json<HashMap<String, String>>, AppError> {
     // Get guild
@@ -174,7 +174,7 @@ async fn delete_guild_invite(
 #[get("/invites/<invite_code>", format = "json")]
 async fn get_invite(
     invite_code: &str,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
 ) -> Result<Json<ReturnedGuild>, AppError> {
     // Get guild
@@ -228,7 +228,7 @@ async fn get_invite(
 #[put("/invites/<invite_code>", format = "json")]
 async fn join_invite(
     invite_code: &str,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
 ) -> Result<Json<ReturnedGuild>, AppError> {
     // Get guild
@@ -0,0 +1,1136 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::structs::{
+    BulkDeleteMessagesBody, Channel, CreateMessageBody, DeletedMessage, EditMessageBody, Member,
+    Message, MessageRevision, MessageSearchResult, ReadState, SearchMessagesQuery,
+};
+use crate::{
+    routes::channels::{stop_typing, TypingStates},
+    utils::{
+        self,
+        messages::Destination,
+        permissions::{check_channel_permission, ChannelPermissions},
+        subscriptions::Dispatcher,
+        unfurl::Unfurler,
+    },
+    AppError, Auth,
+};
+
+use rocket::{
+    http::Status,
+    serde::json::{from_value, Json, Value},
+    Route, State,
+};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+// Maximum length of a search query
+const MAX_QUERY_LENGTH: usize = 200;
+// Maximum amount of messages deleted at once
+const MAX_BULK_DELETE: usize = 500;
+
+#[get(
+    "/guilds/<guild_id>/channels/<channel_id>/messages?<before>&<limit>",
+    format = "json"
+)]
+async fn get_guild_messages(
+    guild_id: &str,
+    channel_id: &str,
+    before: Option<&str>,
+    limit: Option<i64>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<Message>>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    if !channels.iter().any(|channel| channel.id == channel_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if can view the channel
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::VIEW_CHANNEL,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    Ok(Json(
+        utils::messages::get_messages(database, &user_id.0, channel_id, before, limit).await?,
+    ))
+}
+
+#[post(
+    "/guilds/<guild_id>/channels/<channel_id>/messages",
+    format = "json",
+    data = "<body>"
+)]
+#[allow(clippy::too_many_arguments)]
+async fn create_guild_message(
+    guild_id: &str,
+    channel_id: &str,
+    body: Json<CreateMessageBody>,
+    typing_states: &State<TypingStates>,
+    unfurler: &State<Unfurler>,
+    dispatcher: &State<Dispatcher>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Message>, AppError> {
+    // Check if content is empty or too long
+    if body.content.trim().is_empty() || body.content.len() > utils::messages::MAX_CONTENT_LENGTH {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    if !channels.iter().any(|channel| channel.id == channel_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if can send messages
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::SEND_MESSAGES,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Check if the channel isn't an archived thread
+    if channels.iter().any(|channel| {
+        channel.id == channel_id
+            && channel
+                .thread
+                .as_ref()
+                .is_some_and(|thread| thread.archived)
+    }) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    let mut message = utils::messages::resolve_guild_mentions(
+        &guild,
+        check_channel_permission(
+            &guild,
+            &channel_id.to_string(),
+            &user_id.0,
+            ChannelPermissions::MENTION_EVERYONE,
+        ),
+        utils::messages::new_message(&user_id.0, &body.content, "default"),
+    );
+
+    if let Some(reply_to) = &body.reply_to {
+        message = utils::messages::reply(
+            database,
+            channel_id,
+            reply_to,
+            body.mention_author.unwrap_or(true),
+            message,
+        )
+        .await?;
+    }
+
+    let message =
+        utils::messages::create_message(database, channel_id, Some(guild_id), message).await?;
+
+    stop_typing(typing_states, sse_clients, channel_id, &user_id.0).await;
+
+    utils::messages::send_message(
+        database,
+        sse_clients,
+        dispatcher,
+        unfurler,
+        Destination::Guild(&guild),
+        channel_id,
+        &message,
+    )
+    .await?;
+
+    Ok(Json(message))
+}
+
+#[get("/guilds/<guild_id>/messages/search?<query..>", format = "json")]
+async fn search_guild_messages(
+    guild_id: &str,
+    query: SearchMessagesQuery,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<MessageSearchResult>>, AppError> {
+    let q = query
+        .q
+        .as_deref()
+        .map(|q| q.trim())
+        .filter(|q| !q.is_empty());
+
+    // Check if query is too long
+    if q.is_some_and(|q| q.len() > MAX_QUERY_LENGTH) {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    // Check if the channel exists and can be viewed
+    if let Some(channel_id) = &query.channel {
+        if !channels.iter().any(|channel| channel.id == *channel_id) {
+            return Err(AppError(Status::NotFound));
+        }
+
+        if !check_channel_permission(
+            &guild,
+            channel_id,
+            &user_id.0,
+            ChannelPermissions::VIEW_CHANNEL,
+        ) {
+            return Err(AppError(Status::Forbidden));
+        }
+    }
+
+    // Only search the channels the user can view
+    let searched: Vec<Uuid> = channels
+        .iter()
+        .filter(|channel| query.channel.as_ref().is_none_or(|id| channel.id == *id))
+        .filter(|channel| {
+            check_channel_permission(
+                &guild,
+                &channel.id,
+                &user_id.0,
+                ChannelPermissions::VIEW_CHANNEL,
+            )
+        })
+        .map(|channel| Uuid::parse_str(&channel.id).unwrap())
+        .collect();
+
+    // The snippet is the HTML-escaped content, with matches wrapped in <mark></mark>
+    let messages = database
+        .query(
+            "SELECT *, CASE WHEN $1::text IS NULL THEN NULL ELSE ts_headline(
+                'simple',
+                replace(replace(replace(replace(replace(content,
+                    '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '\"', '&quot;'), '''', '&#39;'),
+                websearch_to_tsquery('simple', $1),
+                'StartSel=<mark>, StopSel=</mark>, MaxFragments=3, MaxWords=20, MinWords=5'
+            ) END AS snippet
+            FROM messages WHERE guild = $2 AND channel = ANY($3) AND deleted IS NULL
+            AND ($1::text IS NULL OR to_tsvector('simple', content) @@ websearch_to_tsquery('simple', $1))
+            AND ($4::text IS NULL OR author = $4)
+            AND ($5::bool IS NULL OR (atachment IS NOT NULL) = $5)
+            AND ($6::bool IS NULL OR (content ~* 'https?://') = $6)
+            AND ($7::text IS NULL OR $7 = any(mentions))
+            AND ($8::bigint IS NULL OR creation >= $8)
+            AND ($9::bigint IS NULL OR creation < $9)
+            AND ($10::uuid IS NULL OR (creation, id) < (
+                SELECT creation, id FROM messages WHERE id = $10
+            )) ORDER BY creation DESC, id DESC LIMIT $11",
+            &[
+                &q,
+                &Uuid::parse_str(guild_id).unwrap(),
+                &searched,
+                &query.author,
+                &query.has_attachment,
+                &query.has_link,
+                &query.mentions,
+                &query.since,
+                &query.until,
+                &query
+                    .before
+                    .as_ref()
+                    .and_then(|before| Uuid::parse_str(before).ok()),
+                &query
+                    .limit
+                    .unwrap_or(utils::messages::DEFAULT_LIMIT)
+                    .clamp(1, utils::messages::MAX_LIMIT),
+            ],
+        )
+        .await?;
+
+    let parsed = utils::reactions::attach_reactions(
+        database,
+        &user_id.0,
+        messages
+            .iter()
+            .map(utils::messages::parse_message)
+            .collect(),
+    )
+    .await?;
+
+    Ok(Json(
+        messages
+            .iter()
+            .zip(parsed)
+            .map(|(row, message)| MessageSearchResult {
+                channel_id: row.get::<&str, Uuid>("channel").to_string(),
+                message,
+                snippet: row
+                    .try_get::<&str, Option<String>>("snippet")
+                    .unwrap_or(None),
+            })
+            .collect(),
+    ))
+}
+
+#[post(
+    "/guilds/<guild_id>/channels/<channel_id>/messages/<message_id>/ack",
+    format = "json"
+)]
+async fn ack_guild_message(
+    guild_id: &str,
+    channel_id: &str,
+    message_id: &str,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReadState>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    if !channels.iter().any(|channel| channel.id == channel_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if can view the channel
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::VIEW_CHANNEL,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Get message
+    let pre_message = database
+        .query_one(
+            "SELECT * FROM messages WHERE id = $1 AND channel = $2 AND deleted IS NULL",
+            &[
+                &Uuid::parse_str(message_id).unwrap(),
+                &Uuid::parse_str(channel_id).unwrap(),
+            ],
+        )
+        .await;
+
+    if pre_message.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let message = utils::messages::parse_message(&pre_message.unwrap());
+
+    let members: Vec<Member> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("members"))).unwrap();
+    let roles = members
+        .into_iter()
+        .find(|member| member.id == user_id.0)
+        .unwrap()
+        .roles;
+
+    let read_state = utils::read_states::ack(
+        database,
+        &user_id.0,
+        channel_id,
+        Some(guild_id),
+        &roles,
+        &message,
+    )
+    .await?;
+
+    // Broadcast channelAcked event to every session of the user
+    utils::sse::broadcast(
+        sse_clients,
+        &user_id.0,
+        utils::structs::SSEEvent::ChannelAcked {
+            guild_id: Some(guild_id),
+            read_state: &read_state,
+        },
+    )
+    .await;
+
+    Ok(Json(read_state))
+}
+
+#[patch(
+    "/guilds/<guild_id>/channels/<channel_id>/messages/<message_id>",
+    format = "json",
+    data = "<body>"
+)]
+#[allow(clippy::too_many_arguments)]
+async fn edit_guild_message(
+    guild_id: &str,
+    channel_id: &str,
+    message_id: &str,
+    body: Json<EditMessageBody>,
+    unfurler: &State<Unfurler>,
+    dispatcher: &State<Dispatcher>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Message>, AppError> {
+    // Check if content is empty or too long
+    if body.content.trim().is_empty() || body.content.len() > utils::messages::MAX_CONTENT_LENGTH {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    if !channels.iter().any(|channel| channel.id == channel_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if can view the channel
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::VIEW_CHANNEL,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Get message
+    let pre_message = database
+        .query_one(
+            "SELECT * FROM messages WHERE id = $1 AND channel = $2 AND deleted IS NULL",
+            &[
+                &Uuid::parse_str(message_id).unwrap(),
+                &Uuid::parse_str(channel_id).unwrap(),
+            ],
+        )
+        .await;
+
+    if pre_message.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let row = pre_message.unwrap();
+    let previous = utils::messages::parse_message(&row);
+
+    // Only the author can edit their own messages
+    if previous.author != user_id.0 || previous.r#type != "default" {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    let message = utils::messages::resolve_guild_mentions(
+        &guild,
+        check_channel_permission(
+            &guild,
+            &channel_id.to_string(),
+            &user_id.0,
+            ChannelPermissions::MENTION_EVERYONE,
+        ),
+        utils::messages::edited_message(&row, &body.content),
+    );
+
+    let message =
+        utils::messages::edit_message(database, Some(guild_id), &previous, message).await?;
+
+    let recipients = utils::messages::get_recipients(&guild, channel_id);
+
+    // Broadcast messageUpdated event to every member that can view the channel
+    for recipient in recipients.iter() {
+        utils::sse::broadcast(
+            sse_clients,
+            recipient,
+            utils::structs::SSEEvent::MessageUpdated {
+                guild_id: Some(guild_id),
+                channel_id,
+                message: &message,
+            },
+        )
+        .await;
+    }
+
+    // Dispatch messageUpdated event to the guild's subscriptions
+    utils::subscriptions::dispatch(
+        dispatcher,
+        guild_id,
+        utils::structs::SSEEvent::MessageUpdated {
+            guild_id: Some(guild_id),
+            channel_id,
+            message: &message,
+        },
+    );
+
+    utils::unfurl::queue(unfurler, Some(guild_id), channel_id, &message, recipients);
+
+    Ok(Json(
+        utils::reactions::attach_reactions(database, &user_id.0, vec![message])
+            .await?
+            .remove(0),
+    ))
+}
+
+#[delete(
+    "/guilds/<guild_id>/channels/<channel_id>/messages/<message_id>",
+    format = "json"
+)]
+async fn delete_guild_message(
+    guild_id: &str,
+    channel_id: &str,
+    message_id: &str,
+    dispatcher: &State<Dispatcher>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    if !channels.iter().any(|channel| channel.id == channel_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if can view the channel
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::VIEW_CHANNEL,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Get message
+    let pre_message = database
+        .query_one(
+            "SELECT * FROM messages WHERE id = $1 AND channel = $2 AND deleted IS NULL",
+            &[
+                &Uuid::parse_str(message_id).unwrap(),
+                &Uuid::parse_str(channel_id).unwrap(),
+            ],
+        )
+        .await;
+
+    if pre_message.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let message = utils::messages::parse_message(&pre_message.unwrap());
+
+    // Check if the author, or can manage messages
+    if message.author != user_id.0
+        && !check_channel_permission(
+            &guild,
+            &channel_id.to_string(),
+            &user_id.0,
+            ChannelPermissions::MANAGE_MESSAGES,
+        )
+    {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    utils::messages::delete_message(database, message_id, &user_id.0).await?;
+
+    utils::pins::remove(
+        database,
+        sse_clients,
+        &guild,
+        guild_id,
+        channel_id,
+        &[message_id.to_string()],
+    )
+    .await?;
+
+    // Broadcast messageDeleted event to every member that can view the channel
+    for recipient in utils::messages::get_recipients(&guild, channel_id).iter() {
+        utils::sse::broadcast(
+            sse_clients,
+            recipient,
+            utils::structs::SSEEvent::MessageDeleted {
+                guild_id: Some(guild_id),
+                channel_id,
+                message_id,
+            },
+        )
+        .await;
+    }
+
+    // Dispatch messageDeleted event to the guild's subscriptions
+    utils::subscriptions::dispatch(
+        dispatcher,
+        guild_id,
+        utils::structs::SSEEvent::MessageDeleted {
+            guild_id: Some(guild_id),
+            channel_id,
+            message_id,
+        },
+    );
+
+    Ok(Json(HashMap::new()))
+}
+
+#[post(
+    "/guilds/<guild_id>/channels/<channel_id>/messages/bulk-delete",
+    format = "json",
+    data = "<body>"
+)]
+#[allow(clippy::too_many_arguments)]
+async fn bulk_delete_guild_messages(
+    guild_id: &str,
+    channel_id: &str,
+    body: Json<BulkDeleteMessagesBody>,
+    dispatcher: &State<Dispatcher>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<String>>, AppError> {
+    // Check if there is at least one criteria, and not too many messages
+    if (body.messages.is_none()
+        && body.author.is_none()
+        && body.since.is_none()
+        && body.until.is_none())
+        || body
+            .messages
+            .as_ref()
+            .is_some_and(|messages| messages.is_empty() || messages.len() > MAX_BULK_DELETE)
+    {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Check if every message id is valid
+    let message_ids = match &body.messages {
+        Some(messages) => {
+            let ids: Vec<Uuid> = messages
+                .iter()
+                .filter_map(|message| Uuid::parse_str(message).ok())
+                .collect();
+
+            if ids.len() != messages.len() {
+                return Err(AppError(Status::BadRequest));
+            }
+
+            Some(ids)
+        }
+        None => None,
+    };
+
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    if !channels.iter().any(|channel| channel.id == channel_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if can manage messages
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::MANAGE_MESSAGES,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Delete the matching messages, newest first, and record it in the audit log in a single statement
+    let deleted = database
+        .query(
+            "WITH deleted AS (
+                UPDATE messages SET deleted = $1, deleted_by = $2 WHERE id IN (
+                    SELECT id FROM messages WHERE channel = $3 AND deleted IS NULL
+                    AND ($4::uuid[] IS NULL OR id = any($4))
+                    AND ($5::text IS NULL OR author = $5)
+                    AND ($6::bigint IS NULL OR creation >= $6)
+                    AND ($7::bigint IS NULL OR creation < $7)
+                    ORDER BY creation DESC, id DESC LIMIT $8
+                ) RETURNING id
+            ), logged AS (
+                INSERT INTO audit_log (id, guild, author, action, target, details, creation)
+                SELECT $9, $10, $2, 'messagesBulkDeleted', $3::text, jsonb_strip_nulls(jsonb_build_object(
+                    'count', count(*), 'author', $5::text, 'since', $6::bigint, 'until', $7::bigint
+                )), $1
+                FROM deleted HAVING count(*) > 0
+            )
+            SELECT id FROM deleted",
+            &[
+                &(SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs() as i64),
+                &user_id.0,
+                &Uuid::parse_str(channel_id).unwrap(),
+                &message_ids,
+                &body.author,
+                &body.since,
+                &body.until,
+                &(MAX_BULK_DELETE as i64),
+                &Uuid::new_v4(),
+                &Uuid::parse_str(guild_id).unwrap(),
+            ],
+        )
+        .await?;
+
+    let message_ids: Vec<String> = deleted
+        .iter()
+        .map(|message| message.get::<&str, Uuid>("id").to_string())
+        .collect();
+
+    if message_ids.is_empty() {
+        return Ok(Json(message_ids));
+    }
+
+    utils::pins::remove(
+        database,
+        sse_clients,
+        &guild,
+        guild_id,
+        channel_id,
+        &message_ids,
+    )
+    .await?;
+
+    // Broadcast messagesBulkDeleted event to every member that can view the channel
+    for recipient in utils::messages::get_recipients(&guild, channel_id).iter() {
+        utils::sse::broadcast(
+            sse_clients,
+            recipient,
+            utils::structs::SSEEvent::MessagesBulkDeleted {
+                guild_id,
+                channel_id,
+                message_ids: &message_ids,
+            },
+        )
+        .await;
+    }
+
+    // Dispatch messagesBulkDeleted event to the guild's subscriptions
+    utils::subscriptions::dispatch(
+        dispatcher,
+        guild_id,
+        utils::structs::SSEEvent::MessagesBulkDeleted {
+            guild_id,
+            channel_id,
+            message_ids: &message_ids,
+        },
+    );
+
+    Ok(Json(message_ids))
+}
+
+#[get(
+    "/guilds/<guild_id>/channels/<channel_id>/messages/<message_id>/revisions",
+    format = "json"
+)]
+async fn get_guild_message_revisions(
+    guild_id: &str,
+    channel_id: &str,
+    message_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<MessageRevision>>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    if !channels.iter().any(|channel| channel.id == channel_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if can manage messages
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::MANAGE_MESSAGES,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Check if the message exists in the channel, even if deleted
+    if database
+        .query_one(
+            "SELECT * FROM messages WHERE id = $1 AND channel = $2",
+            &[
+                &Uuid::parse_str(message_id).unwrap(),
+                &Uuid::parse_str(channel_id).unwrap(),
+            ],
+        )
+        .await
+        .is_err()
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Get previous revisions, newest first
+    let revisions = database
+        .query(
+            "SELECT * FROM message_revisions WHERE message = $1 ORDER BY creation DESC, id DESC",
+            &[&Uuid::parse_str(message_id).unwrap()],
+        )
+        .await?;
+
+    Ok(Json(
+        revisions
+            .iter()
+            .map(|revision| MessageRevision {
+                content: revision.get::<&str, String>("content"),
+                creation: revision.get::<&str, i64>("creation"),
+            })
+            .collect(),
+    ))
+}
+
+#[get(
+    "/guilds/<guild_id>/channels/<channel_id>/messages/deleted?<before>&<limit>",
+    format = "json"
+)]
+async fn get_deleted_guild_messages(
+    guild_id: &str,
+    channel_id: &str,
+    before: Option<&str>,
+    limit: Option<i64>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<DeletedMessage>>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    if !channels.iter().any(|channel| channel.id == channel_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if can manage messages
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::MANAGE_MESSAGES,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Get messages deleted within the retention period, most recently deleted first
+    let messages = database
+        .query(
+            "SELECT * FROM messages WHERE channel = $1 AND deleted >= $2 AND (
+                $3::uuid IS NULL OR (deleted, id) < (
+                    SELECT deleted, id FROM messages WHERE id = $3
+                )
+            ) ORDER BY deleted DESC, id DESC LIMIT $4",
+            &[
+                &Uuid::parse_str(channel_id).unwrap(),
+                &(SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs() as i64
+                    - utils::messages::DELETED_RETENTION),
+                &before.and_then(|before| Uuid::parse_str(before).ok()),
+                &limit
+                    .unwrap_or(utils::messages::DEFAULT_LIMIT)
+                    .clamp(1, utils::messages::MAX_LIMIT),
+            ],
+        )
+        .await?;
+
+    Ok(Json(
+        messages
+            .iter()
+            .map(|message| DeletedMessage {
+                message: utils::messages::parse_message(message),
+                deleted: message.get::<&str, i64>("deleted"),
+                deleted_by: message.get::<&str, String>("deleted_by"),
+            })
+            .collect(),
+    ))
+}
+
+#[post(
+    "/guilds/<guild_id>/channels/<channel_id>/messages/<message_id>/restore",
+    format = "json"
+)]
+async fn restore_guild_message(
+    guild_id: &str,
+    channel_id: &str,
+    message_id: &str,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Message>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    if !channels.iter().any(|channel| channel.id == channel_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if can manage messages
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::MANAGE_MESSAGES,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Restore the message if deleted within the retention period
+    let pre_message = database
+        .query_one(
+            "UPDATE messages SET deleted = NULL, deleted_by = NULL
+            WHERE id = $1 AND channel = $2 AND deleted >= $3 RETURNING *",
+            &[
+                &Uuid::parse_str(message_id).unwrap(),
+                &Uuid::parse_str(channel_id).unwrap(),
+                &(SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs() as i64
+                    - utils::messages::DELETED_RETENTION),
+            ],
+        )
+        .await;
+
+    if pre_message.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let message = utils::reactions::attach_reactions(
+        database,
+        &user_id.0,
+        vec![utils::messages::parse_message(&pre_message.unwrap())],
+    )
+    .await?
+    .remove(0);
+
+    // Broadcast messageRestored event to every member that can view the channel
+    for recipient in utils::messages::get_recipients(&guild, channel_id).iter() {
+        utils::sse::broadcast(
+            sse_clients,
+            recipient,
+            utils::structs::SSEEvent::MessageRestored {
+                guild_id: Some(guild_id),
+                channel_id,
+                message: &message,
+            },
+        )
+        .await;
+    }
+
+    Ok(Json(message))
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![
+        get_guild_messages,
+        create_guild_message,
+        search_guild_messages,
+        ack_guild_message,
+        edit_guild_message,
+        delete_guild_message,
+        bulk_delete_guild_messages,
+        get_guild_message_revisions,
+        get_deleted_guild_messages,
+        restore_guild_message
+    ]
+}
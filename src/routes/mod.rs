@@ -21,21 +21,58 @@ use crate::utils;
 pub mod structs;
 
 pub mod account;
+pub mod applications;
+pub mod attachments;
+pub mod audit_log;
+pub mod channels;
+pub mod commands;
+pub mod discovery;
+pub mod dms;
+pub mod emojis;
 pub mod experimenting;
 pub mod guilds;
+pub mod images;
+pub mod interactions;
 pub mod invites;
+pub mod messages;
+pub mod oauth2;
+pub mod pins;
+pub mod reactions;
+pub mod relationships;
+pub mod subscriptions;
+pub mod threads;
 pub mod users;
+pub mod webhooks;
 
 // Return routes
 pub fn get_routes() -> Vec<rocket::Route> {
     let mut routes = Vec::new();
     routes.extend(utils::sse::get_route());
+    routes.extend(utils::gateway::get_route());
 
     routes.extend(experimenting::get_routes());
     routes.extend(account::get_routes());
     routes.extend(users::get_routes());
+    routes.extend(applications::get_routes());
+    routes.extend(audit_log::get_routes());
+    routes.extend(commands::get_routes());
+    routes.extend(discovery::get_routes());
+    routes.extend(emojis::get_routes());
+    routes.extend(interactions::get_routes());
+    routes.extend(oauth2::get_routes());
+    routes.extend(pins::get_routes());
+    routes.extend(reactions::get_routes());
     routes.extend(guilds::get_routes());
+    routes.extend(channels::get_routes());
+    routes.extend(messages::get_routes());
+    routes.extend(dms::get_routes());
+    routes.extend(attachments::get_routes());
+    routes.extend(images::get_routes());
+    routes.extend(relationships::get_routes());
     routes.extend(invites::get_routes());
+    routes.extend(subscriptions::get_routes());
+    routes.extend(threads::get_routes());
+    routes.extend(webhooks::get_routes());
 
     routes
 }
@@ -0,0 +1,426 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::structs::{
+    AuthorizeBody, AuthorizeResp, IntrospectResp, ReturnedAuthorization, ReturnedConsent,
+    ReturnedUser, TokenForm, TokenRequestForm, TokenResp,
+};
+use crate::{
+    utils::oauth::{generate_token, hash_token, is_valid_redirect_uri, SCOPES},
+    AppError, Auth,
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use reqwest::Url;
+use rocket::{form::Form, http::Status, serde::json::Json, Route, State};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+// Seconds an authorization code stays valid
+const CODE_TTL: i64 = 10 * 60;
+// Seconds access and refresh tokens stay valid
+const ACCESS_TOKEN_TTL: i64 = 60 * 60;
+const REFRESH_TOKEN_TTL: i64 = 30 * 24 * 60 * 60;
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+// Check the client, its redirect URI and the requested scopes
+async fn validate_request(
+    database: &tokio_postgres::Client,
+    client_id: &str,
+    redirect_uri: &str,
+    scope: &str,
+) -> Result<(Row, Vec<String>), AppError> {
+    // Check if the redirect URI is still valid, in case it was saved before being disallowed
+    if !is_valid_redirect_uri(redirect_uri) {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Get client
+    let pre_client = database
+        .query_one(
+            "SELECT * FROM users WHERE id = $1 AND type = 'BOT' AND $2 = any(redirect_uris)",
+            &[&Uuid::parse_str(client_id).unwrap(), &redirect_uri],
+        )
+        .await;
+
+    if pre_client.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let mut scopes: Vec<String> = scope
+        .split_whitespace()
+        .map(|scope| scope.to_string())
+        .collect();
+    scopes.sort();
+    scopes.dedup();
+
+    // Check if scopes are valid
+    if scopes.is_empty() || !scopes.iter().all(|scope| SCOPES.contains(&scope.as_str())) {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    Ok((pre_client.unwrap(), scopes))
+}
+
+// Create an access and refresh token pair
+async fn issue_tokens(
+    database: &tokio_postgres::Client,
+    client_id: &str,
+    owner: &str,
+    scopes: &Vec<String>,
+) -> Result<TokenResp, AppError> {
+    let access_token = generate_token();
+    let refresh_token = generate_token();
+
+    database.execute("INSERT INTO oauth_tokens (token, refresh, client, owner, scopes, expiration, refresh_expiration) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    &[
+        &hash_token(&access_token),
+        &hash_token(&refresh_token),
+        &client_id,
+        &owner,
+        scopes,
+        &(now() + ACCESS_TOKEN_TTL),
+        &(now() + REFRESH_TOKEN_TTL),
+    ]).await?;
+
+    Ok(TokenResp {
+        access_token,
+        token_type: "Bearer".to_string(),
+        expires_in: ACCESS_TOKEN_TTL,
+        refresh_token,
+        scope: scopes.join(" "),
+    })
+}
+
+#[get(
+    "/oauth2/authorize?<client_id>&<redirect_uri>&<scope>",
+    format = "json"
+)]
+async fn get_authorization(
+    client_id: &str,
+    redirect_uri: &str,
+    scope: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedAuthorization>, AppError> {
+    let (client, scopes) = validate_request(database, client_id, redirect_uri, scope).await?;
+
+    // Check if the user already consented to every scope
+    let consented = database
+        .query_one(
+            "SELECT * FROM oauth_consents WHERE owner = $1 AND client = $2 AND scopes @> $3",
+            &[&user_id.0, &client_id, &scopes],
+        )
+        .await
+        .is_ok();
+
+    Ok(Json(ReturnedAuthorization {
+        application: ReturnedUser {
+            id: client.get::<&str, Uuid>("id").to_string(),
+            username: client.get::<&str, String>("username"),
+            discriminator: client.get::<&str, String>("discriminator"),
+            avatar: client
+                .try_get::<&str, Option<String>>("avatar")
+                .unwrap_or(None),
+            about: client
+                .try_get::<&str, Option<String>>("about")
+                .unwrap_or(None),
+            creation: client.get::<&str, i64>("creation"),
+            bot: true,
+        },
+        scopes,
+        consented,
+    }))
+}
+
+#[post("/oauth2/authorize", format = "json", data = "<body>")]
+async fn authorize(
+    body: Json<AuthorizeBody>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<AuthorizeResp>, AppError> {
+    // Check if the PKCE challenge is valid, only S256 is supported
+    if body.code_challenge_method != "S256"
+        || body.code_challenge.len() != 43
+        || URL_SAFE_NO_PAD.decode(&body.code_challenge).is_err()
+    {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    let (_, scopes) =
+        validate_request(database, &body.client_id, &body.redirect_uri, &body.scope).await?;
+
+    // Check if the user isn't a bot
+    if database
+        .query_one(
+            "SELECT * FROM users WHERE id = $1 AND type = 'USER'",
+            &[&Uuid::parse_str(&user_id.0).unwrap()],
+        )
+        .await
+        .is_err()
+    {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Record the consent, keeping previously granted scopes
+    database
+        .execute(
+            "INSERT INTO oauth_consents (owner, client, scopes, creation) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (owner, client) DO UPDATE SET scopes = (
+                SELECT array_agg(DISTINCT scope) FROM unnest(oauth_consents.scopes || $3) AS scope
+            )",
+            &[&user_id.0, &body.client_id, &scopes, &now()],
+        )
+        .await?;
+
+    // Create code
+    let code = generate_token();
+    database.execute("INSERT INTO oauth_codes (code, client, owner, redirect_uri, scopes, challenge, expiration) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    &[
+        &hash_token(&code),
+        &body.client_id,
+        &user_id.0,
+        &body.redirect_uri,
+        &scopes,
+        &body.code_challenge,
+        &(now() + CODE_TTL),
+    ]).await?;
+
+    let mut redirect = Url::parse(&body.redirect_uri).unwrap();
+    redirect.query_pairs_mut().append_pair("code", &code);
+
+    if let Some(state) = &body.state {
+        redirect.query_pairs_mut().append_pair("state", state);
+    }
+
+    Ok(Json(AuthorizeResp {
+        redirect: redirect.to_string(),
+    }))
+}
+
+#[post("/oauth2/token", data = "<body>")]
+async fn token(
+    body: Form<TokenForm>,
+    database: &State<crate::Database>,
+) -> Result<Json<TokenResp>, AppError> {
+    match body.grant_type.as_str() {
+        "authorization_code" => {
+            let (Some(code), Some(redirect_uri), Some(code_verifier)) =
+                (&body.code, &body.redirect_uri, &body.code_verifier)
+            else {
+                return Err(AppError(Status::BadRequest));
+            };
+
+            // Codes can only be used once
+            let pre_code = database
+                .query_one(
+                    "DELETE FROM oauth_codes WHERE code = $1 AND client = $2 AND expiration > $3
+                    AND EXISTS (SELECT 1 FROM users WHERE users.id::text = oauth_codes.client AND users.type = 'BOT') RETURNING *",
+                    &[&hash_token(code), &body.client_id, &now()],
+                )
+                .await;
+
+            if pre_code.is_err() {
+                return Err(AppError(Status::BadRequest));
+            }
+
+            let code = pre_code.unwrap();
+
+            // Check if the redirect URI and verifier match the authorization
+            if code.get::<&str, String>("redirect_uri") != *redirect_uri
+                || URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+                    != code.get::<&str, String>("challenge")
+            {
+                return Err(AppError(Status::BadRequest));
+            }
+
+            Ok(Json(
+                issue_tokens(
+                    database,
+                    &body.client_id,
+                    &code.get::<&str, String>("owner"),
+                    &code.get::<&str, Vec<String>>("scopes"),
+                )
+                .await?,
+            ))
+        }
+        "refresh_token" => {
+            let Some(refresh_token) = &body.refresh_token else {
+                return Err(AppError(Status::BadRequest));
+            };
+
+            // Refresh tokens are rotated on use
+            let pre_token = database
+                .query_one(
+                    "DELETE FROM oauth_tokens WHERE refresh = $1 AND client = $2 AND refresh_expiration > $3
+                    AND EXISTS (SELECT 1 FROM users WHERE users.id::text = oauth_tokens.client AND users.type = 'BOT') RETURNING *",
+                    &[&hash_token(refresh_token), &body.client_id, &now()],
+                )
+                .await;
+
+            if pre_token.is_err() {
+                return Err(AppError(Status::BadRequest));
+            }
+
+            let token = pre_token.unwrap();
+
+            Ok(Json(
+                issue_tokens(
+                    database,
+                    &body.client_id,
+                    &token.get::<&str, String>("owner"),
+                    &token.get::<&str, Vec<String>>("scopes"),
+                )
+                .await?,
+            ))
+        }
+        _ => Err(AppError(Status::BadRequest)),
+    }
+}
+
+#[post("/oauth2/introspect", data = "<body>")]
+async fn introspect(
+    body: Form<TokenRequestForm>,
+    database: &State<crate::Database>,
+) -> Result<Json<IntrospectResp>, AppError> {
+    // Only the client a token was issued to can inspect it
+    let token = database
+        .query_one(
+            "SELECT * FROM oauth_tokens WHERE token = $1 AND client = $2 AND expiration > $3
+            AND EXISTS (SELECT 1 FROM users WHERE users.id::text = oauth_tokens.client AND users.type = 'BOT')",
+            &[&hash_token(&body.token), &body.client_id, &now()],
+        )
+        .await;
+
+    Ok(Json(match token {
+        Ok(token) => IntrospectResp {
+            active: true,
+            scope: Some(token.get::<&str, Vec<String>>("scopes").join(" ")),
+            client_id: Some(token.get::<&str, String>("client")),
+            user_id: Some(token.get::<&str, String>("owner")),
+            exp: Some(token.get::<&str, i64>("expiration")),
+        },
+        Err(_) => IntrospectResp {
+            active: false,
+            scope: None,
+            client_id: None,
+            user_id: None,
+            exp: None,
+        },
+    }))
+}
+
+#[post("/oauth2/revoke", data = "<body>")]
+async fn revoke(
+    body: Form<TokenRequestForm>,
+    database: &State<crate::Database>,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    // Either token of a pair revokes both
+    database
+        .execute(
+            "DELETE FROM oauth_tokens WHERE (token = $1 OR refresh = $1) AND client = $2",
+            &[&hash_token(&body.token), &body.client_id],
+        )
+        .await?;
+
+    Ok(Json(HashMap::new()))
+}
+
+#[get("/users/@me/oauth2/consents", format = "json")]
+async fn get_consents(
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<ReturnedConsent>>, AppError> {
+    let consents = database
+        .query(
+            "SELECT * FROM oauth_consents WHERE owner = $1 ORDER BY creation",
+            &[&user_id.0],
+        )
+        .await?;
+
+    Ok(Json(
+        consents
+            .iter()
+            .map(|consent| ReturnedConsent {
+                client_id: consent.get::<&str, String>("client"),
+                scopes: consent.get::<&str, Vec<String>>("scopes"),
+                creation: consent.get::<&str, i64>("creation"),
+            })
+            .collect(),
+    ))
+}
+
+#[delete("/users/@me/oauth2/consents/<client_id>", format = "json")]
+async fn del_consent(
+    client_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    // Delete consent
+    if database
+        .execute(
+            "DELETE FROM oauth_consents WHERE owner = $1 AND client = $2",
+            &[&user_id.0, &client_id],
+        )
+        .await?
+        < 1
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Revoke every code and token issued with it
+    database
+        .execute(
+            "DELETE FROM oauth_codes WHERE owner = $1 AND client = $2",
+            &[&user_id.0, &client_id],
+        )
+        .await?;
+
+    database
+        .execute(
+            "DELETE FROM oauth_tokens WHERE owner = $1 AND client = $2",
+            &[&user_id.0, &client_id],
+        )
+        .await?;
+
+    Ok(Json(HashMap::new()))
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![
+        get_authorization,
+        authorize,
+        token,
+        introspect,
+        revoke,
+        get_consents,
+        del_consent
+    ]
+}
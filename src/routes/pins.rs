@@ -0,0 +1,346 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::structs::{Channel, Message};
+use crate::{
+    utils::{
+        self,
+        messages::Destination,
+        permissions::{check_channel_permission, ChannelPermissions},
+        subscriptions::Dispatcher,
+        unfurl::Unfurler,
+    },
+    AppError, Auth,
+};
+
+use rocket::{
+    http::Status,
+    serde::json::{from_value, Json, Value},
+    Route, State,
+};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+// Maximum amount of pinned messages per channel
+const MAX_PINS: usize = 50;
+
+#[get("/guilds/<guild_id>/channels/<channel_id>/pins", format = "json")]
+async fn get_pins(
+    guild_id: &str,
+    channel_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<Message>>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+    let pre_channel = channels
+        .into_iter()
+        .find(|channel| channel.id == channel_id);
+
+    if pre_channel.is_none() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let channel = pre_channel.unwrap();
+
+    // Check if can view the channel
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::VIEW_CHANNEL,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    let pins: Vec<Uuid> = channel
+        .pins
+        .iter()
+        .map(|pin| Uuid::parse_str(pin).unwrap())
+        .collect();
+
+    // Get pinned messages, most recently pinned first
+    let messages = database
+        .query(
+            "SELECT messages.* FROM unnest($1::uuid[]) WITH ORDINALITY AS pin(id, position)
+            JOIN messages ON messages.id = pin.id AND messages.channel = $2 AND messages.deleted IS NULL
+            ORDER BY pin.position DESC",
+            &[&pins, &Uuid::parse_str(channel_id).unwrap()],
+        )
+        .await?;
+
+    Ok(Json(
+        utils::reactions::attach_reactions(
+            database,
+            &user_id.0,
+            messages
+                .iter()
+                .map(utils::messages::parse_message)
+                .collect(),
+        )
+        .await?,
+    ))
+}
+
+#[put(
+    "/guilds/<guild_id>/channels/<channel_id>/pins/<message_id>",
+    format = "json"
+)]
+#[allow(clippy::too_many_arguments)]
+async fn pin_message(
+    guild_id: &str,
+    channel_id: &str,
+    message_id: &str,
+    dispatcher: &State<Dispatcher>,
+    unfurler: &State<Unfurler>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+    let pre_channel = channels
+        .into_iter()
+        .find(|channel| channel.id == channel_id);
+
+    if pre_channel.is_none() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let mut channel = pre_channel.unwrap();
+
+    // Check if can manage messages
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::MANAGE_MESSAGES,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Check if the message exists in the channel
+    if database
+        .query_one(
+            "SELECT * FROM messages WHERE id = $1 AND channel = $2 AND deleted IS NULL",
+            &[
+                &Uuid::parse_str(message_id).unwrap(),
+                &Uuid::parse_str(channel_id).unwrap(),
+            ],
+        )
+        .await
+        .is_err()
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if already pinned
+    if channel.pins.iter().any(|pin| pin == message_id) {
+        return Ok(Json(HashMap::new()));
+    }
+
+    // Check if the channel has too many pins
+    if channel.pins.len() >= MAX_PINS {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Append pin
+    database
+        .execute(
+            "UPDATE guilds SET channels = array_replace(channels,
+                                    (
+                                        SELECT channel
+                                        FROM unnest(channels) AS channel
+                                        WHERE channel->>'id' = $1
+                                    ),
+                                    (
+                                        SELECT jsonb_set(channel, '{pins}', (channel->'pins') || to_jsonb($2::text))
+                                        FROM unnest(channels) AS channel
+                                        WHERE channel->>'id' = $1
+                                    )
+                                )
+            WHERE id = $3",
+            &[
+                &channel_id,
+                &message_id,
+                &Uuid::parse_str(guild_id).unwrap(),
+            ],
+        )
+        .await?;
+
+    channel.pins.push(message_id.to_string());
+
+    // Announce the pin, referencing the pinned message
+    let message = utils::messages::reply(
+        database,
+        channel_id,
+        message_id,
+        false,
+        utils::messages::new_message(&user_id.0, "pinned a message", "pin"),
+    )
+    .await?;
+    let message =
+        utils::messages::create_message(database, channel_id, Some(guild_id), message).await?;
+
+    utils::messages::send_message(
+        database,
+        sse_clients,
+        dispatcher,
+        unfurler,
+        Destination::Guild(&guild),
+        channel_id,
+        &message,
+    )
+    .await?;
+
+    utils::pins::broadcast(sse_clients, &guild, guild_id, channel_id, &channel.pins).await;
+
+    Ok(Json(HashMap::new()))
+}
+
+#[delete(
+    "/guilds/<guild_id>/channels/<channel_id>/pins/<message_id>",
+    format = "json"
+)]
+async fn unpin_message(
+    guild_id: &str,
+    channel_id: &str,
+    message_id: &str,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+    let pre_channel = channels
+        .into_iter()
+        .find(|channel| channel.id == channel_id);
+
+    if pre_channel.is_none() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let mut channel = pre_channel.unwrap();
+
+    // Check if can manage messages
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::MANAGE_MESSAGES,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Check if pinned
+    if !channel.pins.iter().any(|pin| pin == message_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Remove pin
+    database
+        .execute(
+            "UPDATE guilds SET channels = array_replace(channels,
+                                    (
+                                        SELECT channel
+                                        FROM unnest(channels) AS channel
+                                        WHERE channel->>'id' = $1
+                                    ),
+                                    (
+                                        SELECT jsonb_set(channel, '{pins}', (channel->'pins') - $2::text)
+                                        FROM unnest(channels) AS channel
+                                        WHERE channel->>'id' = $1
+                                    )
+                                )
+            WHERE id = $3",
+            &[
+                &channel_id,
+                &message_id,
+                &Uuid::parse_str(guild_id).unwrap(),
+            ],
+        )
+        .await?;
+
+    channel.pins.retain(|pin| pin != message_id);
+
+    utils::pins::broadcast(sse_clients, &guild, guild_id, channel_id, &channel.pins).await;
+
+    Ok(Json(HashMap::new()))
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![get_pins, pin_message, unpin_message]
+}
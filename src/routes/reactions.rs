@@ -0,0 +1,576 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::structs::{Channel, Member, ReturnedUser};
+use crate::{
+    routes::dms::get_sendable_dm_recipients,
+    utils::{
+        self,
+        emojis::parse_custom_emoji,
+        permissions::{check_channel_permission, ChannelPermissions},
+        reactions::{displayed_emoji, is_valid_emoji, stored_emoji, MAX_EMOJIS_PER_MESSAGE},
+    },
+    AppError, Auth,
+};
+
+use rocket::{
+    http::Status,
+    serde::json::{from_value, Json, Value},
+    Route, State,
+};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+// Amount of users returned by default, and at most
+const DEFAULT_LIMIT: i64 = 25;
+const MAX_LIMIT: i64 = 100;
+
+// Get the members that can view the guild channel, if the user has the permission in it
+async fn get_guild_recipients(
+    database: &tokio_postgres::Client,
+    guild_id: &str,
+    channel_id: &str,
+    user_id: &str,
+    permission: ChannelPermissions,
+) -> Result<Vec<String>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    if !channels.iter().any(|channel| channel.id == channel_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if has the permission
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.to_string(),
+        permission,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    let members: Vec<Member> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("members"))).unwrap();
+
+    Ok(members
+        .into_iter()
+        .filter(|member| {
+            check_channel_permission(
+                &guild,
+                &channel_id.to_string(),
+                &member.id,
+                ChannelPermissions::VIEW_CHANNEL,
+            )
+        })
+        .map(|member| member.id)
+        .collect())
+}
+
+// Get the recipients of the DM, if the user is one of them
+async fn get_dm_recipients(
+    database: &tokio_postgres::Client,
+    channel_id: &str,
+    user_id: &str,
+) -> Result<Vec<String>, AppError> {
+    let pre_dm = database
+        .query_one(
+            "SELECT * FROM dms WHERE id = $1 AND $2 = any(recipients)",
+            &[&Uuid::parse_str(channel_id).unwrap(), &user_id],
+        )
+        .await;
+
+    if pre_dm.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    Ok(pre_dm.unwrap().get::<&str, Vec<String>>("recipients"))
+}
+
+// Check if the message exists in the channel
+async fn check_message(
+    database: &tokio_postgres::Client,
+    channel_id: &str,
+    message_id: &str,
+) -> Result<(), AppError> {
+    if database
+        .query_one(
+            "SELECT * FROM messages WHERE id = $1 AND channel = $2 AND deleted IS NULL",
+            &[
+                &Uuid::parse_str(message_id).unwrap(),
+                &Uuid::parse_str(channel_id).unwrap(),
+            ],
+        )
+        .await
+        .is_err()
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    Ok(())
+}
+
+// Get the users that reacted with the emoji, in order
+async fn get_reactors(
+    database: &tokio_postgres::Client,
+    message_id: &str,
+    emoji: &str,
+    after: Option<&str>,
+    limit: Option<i64>,
+) -> Result<Vec<ReturnedUser>, AppError> {
+    let users = database
+        .query(
+            "SELECT users.* FROM reactions JOIN users ON users.id::text = reactions.owner
+            WHERE reactions.message = $1 AND reactions.emoji = $2 AND (
+                $3::text IS NULL OR (reactions.creation, reactions.owner) > (
+                    SELECT creation, owner FROM reactions WHERE message = $1 AND emoji = $2 AND owner = $3
+                )
+            ) ORDER BY reactions.creation, reactions.owner LIMIT $4",
+            &[
+                &Uuid::parse_str(message_id).unwrap(),
+                &stored_emoji(emoji),
+                &after,
+                &limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+            ],
+        )
+        .await?;
+
+    Ok(users
+        .iter()
+        .map(|user| ReturnedUser {
+            id: user.get::<&str, Uuid>("id").to_string(),
+            username: user.get::<&str, String>("username"),
+            discriminator: user.get::<&str, String>("discriminator"),
+            avatar: user
+                .try_get::<&str, Option<String>>("avatar")
+                .unwrap_or(None),
+            about: user
+                .try_get::<&str, Option<String>>("about")
+                .unwrap_or(None),
+            creation: user.get::<&str, i64>("creation"),
+            bot: user.get::<&str, String>("type") == "BOT",
+        })
+        .collect())
+}
+
+// Add the user's reaction and broadcast reactionAdded event
+#[allow(clippy::too_many_arguments)]
+async fn add_reaction(
+    database: &tokio_postgres::Client,
+    sse_clients: &crate::SSEClients,
+    guild_id: Option<&str>,
+    channel_id: &str,
+    message_id: &str,
+    emoji: &str,
+    user_id: &str,
+    recipients: Vec<String>,
+) -> Result<(), AppError> {
+    // Check if the emoji is a unicode emoji, or a custom emoji of the guild
+    let emoji = match (parse_custom_emoji(emoji), guild_id) {
+        (Some(id), Some(guild_id)) => {
+            if database
+                .query_one(
+                    "SELECT * FROM emojis WHERE id = $1 AND guild = $2",
+                    &[&id, &Uuid::parse_str(guild_id).unwrap()],
+                )
+                .await
+                .is_err()
+            {
+                return Err(AppError(Status::BadRequest));
+            }
+
+            id.to_string()
+        }
+        (None, _) if is_valid_emoji(emoji) => emoji.to_string(),
+        _ => return Err(AppError(Status::BadRequest)),
+    };
+
+    check_message(database, channel_id, message_id).await?;
+
+    // Check if the message has too many different emojis
+    let emojis = database
+        .query_one(
+            "SELECT count(DISTINCT emoji) AS count, bool_or(emoji = $2) AS present
+            FROM reactions WHERE message = $1",
+            &[&Uuid::parse_str(message_id).unwrap(), &emoji],
+        )
+        .await?;
+
+    if !emojis.get::<&str, Option<bool>>("present").unwrap_or(false)
+        && emojis.get::<&str, i64>("count") >= MAX_EMOJIS_PER_MESSAGE
+    {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Add reaction, reacting twice does nothing
+    if database
+        .execute(
+            "INSERT INTO reactions (message, channel, guild, owner, emoji, creation) VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT DO NOTHING",
+            &[
+                &Uuid::parse_str(message_id).unwrap(),
+                &Uuid::parse_str(channel_id).unwrap(),
+                &guild_id.map(|guild_id| Uuid::parse_str(guild_id).unwrap()),
+                &user_id,
+                &emoji,
+                &(SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs() as i64),
+            ],
+        )
+        .await?
+        < 1
+    {
+        return Ok(());
+    }
+
+    let emoji = displayed_emoji(database, &emoji).await?;
+
+    // Broadcast reactionAdded event to every recipient
+    for recipient in recipients.iter() {
+        utils::sse::broadcast(
+            sse_clients,
+            recipient,
+            utils::structs::SSEEvent::ReactionAdded {
+                guild_id,
+                channel_id,
+                message_id,
+                emoji: &emoji,
+                user_id,
+            },
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+// Remove the owner's reaction and broadcast reactionRemoved event
+#[allow(clippy::too_many_arguments)]
+async fn remove_reaction(
+    database: &tokio_postgres::Client,
+    sse_clients: &crate::SSEClients,
+    guild_id: Option<&str>,
+    channel_id: &str,
+    message_id: &str,
+    emoji: &str,
+    owner: &str,
+    recipients: Vec<String>,
+) -> Result<(), AppError> {
+    let emoji = stored_emoji(emoji);
+
+    // Delete reaction
+    if database
+        .execute(
+            "DELETE FROM reactions WHERE message = $1 AND channel = $2 AND emoji = $3 AND owner = $4",
+            &[
+                &Uuid::parse_str(message_id).unwrap(),
+                &Uuid::parse_str(channel_id).unwrap(),
+                &emoji,
+                &owner,
+            ],
+        )
+        .await?
+        < 1
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let emoji = displayed_emoji(database, &emoji).await?;
+
+    // Broadcast reactionRemoved event to every recipient
+    for recipient in recipients.iter() {
+        utils::sse::broadcast(
+            sse_clients,
+            recipient,
+            utils::structs::SSEEvent::ReactionRemoved {
+                guild_id,
+                channel_id,
+                message_id,
+                emoji: &emoji,
+                user_id: owner,
+            },
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+#[get(
+    "/guilds/<guild_id>/channels/<channel_id>/messages/<message_id>/reactions/<emoji>?<after>&<limit>",
+    format = "json"
+)]
+#[allow(clippy::too_many_arguments)]
+async fn get_guild_reactions(
+    guild_id: &str,
+    channel_id: &str,
+    message_id: &str,
+    emoji: &str,
+    after: Option<&str>,
+    limit: Option<i64>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<ReturnedUser>>, AppError> {
+    get_guild_recipients(
+        database,
+        guild_id,
+        channel_id,
+        &user_id.0,
+        ChannelPermissions::VIEW_CHANNEL,
+    )
+    .await?;
+
+    check_message(database, channel_id, message_id).await?;
+
+    Ok(Json(
+        get_reactors(database, message_id, emoji, after, limit).await?,
+    ))
+}
+
+#[put(
+    "/guilds/<guild_id>/channels/<channel_id>/messages/<message_id>/reactions/<emoji>/@me",
+    format = "json"
+)]
+async fn add_guild_reaction(
+    guild_id: &str,
+    channel_id: &str,
+    message_id: &str,
+    emoji: &str,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    // Reacting needs the permission to send messages
+    let recipients = get_guild_recipients(
+        database,
+        guild_id,
+        channel_id,
+        &user_id.0,
+        ChannelPermissions::SEND_MESSAGES,
+    )
+    .await?;
+
+    add_reaction(
+        database,
+        sse_clients,
+        Some(guild_id),
+        channel_id,
+        message_id,
+        emoji,
+        &user_id.0,
+        recipients,
+    )
+    .await?;
+
+    Ok(Json(HashMap::new()))
+}
+
+#[delete(
+    "/guilds/<guild_id>/channels/<channel_id>/messages/<message_id>/reactions/<emoji>/@me",
+    format = "json"
+)]
+async fn del_my_guild_reaction(
+    guild_id: &str,
+    channel_id: &str,
+    message_id: &str,
+    emoji: &str,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    let recipients = get_guild_recipients(
+        database,
+        guild_id,
+        channel_id,
+        &user_id.0,
+        ChannelPermissions::VIEW_CHANNEL,
+    )
+    .await?;
+
+    remove_reaction(
+        database,
+        sse_clients,
+        Some(guild_id),
+        channel_id,
+        message_id,
+        emoji,
+        &user_id.0,
+        recipients,
+    )
+    .await?;
+
+    Ok(Json(HashMap::new()))
+}
+
+#[delete(
+    "/guilds/<guild_id>/channels/<channel_id>/messages/<message_id>/reactions/<emoji>/<owner_id>",
+    format = "json",
+    rank = 2
+)]
+#[allow(clippy::too_many_arguments)]
+async fn del_guild_reaction(
+    guild_id: &str,
+    channel_id: &str,
+    message_id: &str,
+    emoji: &str,
+    owner_id: &str,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    // Removing someone else's reaction needs the permission to manage messages
+    let recipients = get_guild_recipients(
+        database,
+        guild_id,
+        channel_id,
+        &user_id.0,
+        ChannelPermissions::MANAGE_MESSAGES,
+    )
+    .await?;
+
+    remove_reaction(
+        database,
+        sse_clients,
+        Some(guild_id),
+        channel_id,
+        message_id,
+        emoji,
+        owner_id,
+        recipients,
+    )
+    .await?;
+
+    Ok(Json(HashMap::new()))
+}
+
+#[get(
+    "/channels/<channel_id>/messages/<message_id>/reactions/<emoji>?<after>&<limit>",
+    format = "json"
+)]
+async fn get_dm_reactions(
+    channel_id: &str,
+    message_id: &str,
+    emoji: &str,
+    after: Option<&str>,
+    limit: Option<i64>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<ReturnedUser>>, AppError> {
+    get_dm_recipients(database, channel_id, &user_id.0).await?;
+
+    check_message(database, channel_id, message_id).await?;
+
+    Ok(Json(
+        get_reactors(database, message_id, emoji, after, limit).await?,
+    ))
+}
+
+#[put(
+    "/channels/<channel_id>/messages/<message_id>/reactions/<emoji>/@me",
+    format = "json"
+)]
+async fn add_dm_reaction(
+    channel_id: &str,
+    message_id: &str,
+    emoji: &str,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    let recipients = get_sendable_dm_recipients(database, channel_id, &user_id.0).await?;
+
+    add_reaction(
+        database,
+        sse_clients,
+        None,
+        channel_id,
+        message_id,
+        emoji,
+        &user_id.0,
+        recipients,
+    )
+    .await?;
+
+    Ok(Json(HashMap::new()))
+}
+
+#[delete(
+    "/channels/<channel_id>/messages/<message_id>/reactions/<emoji>/@me",
+    format = "json"
+)]
+async fn del_my_dm_reaction(
+    channel_id: &str,
+    message_id: &str,
+    emoji: &str,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    let recipients = get_dm_recipients(database, channel_id, &user_id.0).await?;
+
+    remove_reaction(
+        database,
+        sse_clients,
+        None,
+        channel_id,
+        message_id,
+        emoji,
+        &user_id.0,
+        recipients,
+    )
+    .await?;
+
+    Ok(Json(HashMap::new()))
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![
+        get_guild_reactions,
+        add_guild_reaction,
+        del_my_guild_reaction,
+        del_guild_reaction,
+        get_dm_reactions,
+        add_dm_reaction,
+        del_my_dm_reaction
+    ]
+}
@@ -0,0 +1,371 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::structs::{AddFriendBody, PutRelationshipBody, ReturnedRelationship, ReturnedUser};
+use crate::{utils, AppError, Auth};
+
+use rocket::{http::Status, serde::json::Json, Route, State};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+// Check if the blocker has blocked the other user
+pub async fn is_blocked(
+    database: &tokio_postgres::Client,
+    blocker: &str,
+    blocked: &str,
+) -> Result<bool, tokio_postgres::Error> {
+    Ok(database
+        .query_opt(
+            "SELECT * FROM relationships WHERE owner = $1 AND target = $2 AND type = 'blocked'",
+            &[&blocker, &blocked],
+        )
+        .await?
+        .is_some())
+}
+
+async fn get_relationship_type(
+    database: &tokio_postgres::Client,
+    owner: &str,
+    target: &str,
+) -> Result<Option<String>, tokio_postgres::Error> {
+    Ok(database
+        .query_opt(
+            "SELECT * FROM relationships WHERE owner = $1 AND target = $2",
+            &[&owner, &target],
+        )
+        .await?
+        .map(|relationship| relationship.get::<&str, String>("type")))
+}
+
+async fn set_relationship(
+    database: &tokio_postgres::Client,
+    owner: &str,
+    target: &str,
+    r#type: &str,
+) -> Result<(), tokio_postgres::Error> {
+    database
+        .execute(
+            "INSERT INTO relationships (owner, target, type, creation) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (owner, target) DO UPDATE SET type = EXCLUDED.type, creation = EXCLUDED.creation",
+            &[
+                &owner,
+                &target,
+                &r#type,
+                &(SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs() as i64),
+            ],
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn get_returned_relationship(
+    database: &tokio_postgres::Client,
+    owner: &str,
+    target: &str,
+) -> Result<ReturnedRelationship, tokio_postgres::Error> {
+    let relationship = database
+        .query_opt(
+            "SELECT * FROM relationships WHERE owner = $1 AND target = $2",
+            &[&owner, &target],
+        )
+        .await?;
+    let user = database
+        .query_one(
+            "SELECT * FROM users WHERE id = $1",
+            &[&Uuid::parse_str(target).unwrap()],
+        )
+        .await?;
+
+    Ok(ReturnedRelationship {
+        id: target.to_string(),
+        r#type: relationship
+            .as_ref()
+            .map(|relationship| relationship.get::<&str, String>("type"))
+            .unwrap_or("none".to_string()),
+        user: ReturnedUser {
+            id: user.get::<&str, Uuid>("id").to_string(),
+            username: user.get::<&str, String>("username"),
+            discriminator: user.get::<&str, String>("discriminator"),
+            avatar: user
+                .try_get::<&str, Option<String>>("avatar")
+                .unwrap_or(None),
+            about: user
+                .try_get::<&str, Option<String>>("about")
+                .unwrap_or(None),
+            creation: user.get::<&str, i64>("creation"),
+            bot: user.get::<&str, String>("type") == "BOT",
+        },
+        creation: relationship
+            .as_ref()
+            .map(|relationship| relationship.get::<&str, i64>("creation")),
+    })
+}
+
+// Broadcast relationshipUpdated event to both users, each with their own side
+async fn broadcast_relationship(
+    sse_clients: &crate::SSEClients,
+    database: &tokio_postgres::Client,
+    user_id: &str,
+    target_id: &str,
+) -> Result<ReturnedRelationship, tokio_postgres::Error> {
+    let theirs = get_returned_relationship(database, target_id, user_id).await?;
+    utils::sse::broadcast(
+        sse_clients,
+        target_id,
+        utils::structs::SSEEvent::RelationshipUpdated {
+            relationship: &theirs,
+        },
+    )
+    .await;
+
+    let mine = get_returned_relationship(database, user_id, target_id).await?;
+    utils::sse::broadcast(
+        sse_clients,
+        user_id,
+        utils::structs::SSEEvent::RelationshipUpdated {
+            relationship: &mine,
+        },
+    )
+    .await;
+
+    Ok(mine)
+}
+
+// Send a friend request, or accept the incoming one
+async fn request_friend(
+    sse_clients: &crate::SSEClients,
+    database: &tokio_postgres::Client,
+    user_id: &str,
+    target_id: &str,
+) -> Result<ReturnedRelationship, AppError> {
+    // Check if either user blocked the other
+    if is_blocked(database, target_id, user_id).await?
+        || is_blocked(database, user_id, target_id).await?
+    {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    match get_relationship_type(database, user_id, target_id)
+        .await?
+        .as_deref()
+    {
+        // Accept the incoming request
+        Some("incoming") => {
+            set_relationship(database, user_id, target_id, "friend").await?;
+            set_relationship(database, target_id, user_id, "friend").await?;
+        }
+        // Already friends or requested
+        Some(_) => {
+            return Ok(get_returned_relationship(database, user_id, target_id).await?);
+        }
+        None => {
+            set_relationship(database, user_id, target_id, "outgoing").await?;
+            set_relationship(database, target_id, user_id, "incoming").await?;
+        }
+    }
+
+    Ok(broadcast_relationship(sse_clients, database, user_id, target_id).await?)
+}
+
+#[get("/users/@me/relationships", format = "json")]
+async fn get_relationships(
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<ReturnedRelationship>>, AppError> {
+    // Get relationships
+    let relationships = database
+        .query(
+            "SELECT * FROM relationships WHERE owner = $1",
+            &[&user_id.0],
+        )
+        .await?;
+
+    // Parse relationships
+    let mut returned_relationships: Vec<ReturnedRelationship> = Vec::new();
+    for relationship in relationships.iter() {
+        returned_relationships.push(
+            get_returned_relationship(
+                database,
+                &user_id.0,
+                &relationship.get::<&str, String>("target"),
+            )
+            .await?,
+        );
+    }
+
+    Ok(Json(returned_relationships))
+}
+
+#[post("/users/@me/relationships", format = "json", data = "<body>")]
+async fn add_friend(
+    body: Json<AddFriendBody>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedRelationship>, AppError> {
+    // Get user by username#discriminator
+    let pre_target = database
+        .query_one(
+            "SELECT * FROM users WHERE username = $1 AND discriminator = $2",
+            &[&body.username, &body.discriminator],
+        )
+        .await;
+
+    if pre_target.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let target_id = pre_target.unwrap().get::<&str, Uuid>("id").to_string();
+
+    // Check if adding themselves
+    if target_id == user_id.0 {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    Ok(Json(
+        request_friend(sse_clients, database, &user_id.0, &target_id).await?,
+    ))
+}
+
+#[get("/users/@me/relationships/<target_id>", format = "json")]
+async fn get_relationship(
+    target_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedRelationship>, AppError> {
+    // Check if user exists
+    if database
+        .query_one(
+            "SELECT * FROM users WHERE id = $1",
+            &[&Uuid::parse_str(target_id).unwrap()],
+        )
+        .await
+        .is_err()
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    Ok(Json(
+        get_returned_relationship(database, &user_id.0, target_id).await?,
+    ))
+}
+
+#[put(
+    "/users/@me/relationships/<target_id>",
+    format = "json",
+    data = "<body>"
+)]
+async fn put_relationship(
+    target_id: &str,
+    body: Json<PutRelationshipBody>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedRelationship>, AppError> {
+    // Check if type is valid, or if targeting themselves
+    if (body.r#type.is_some()
+        && !["friend", "blocked"].contains(&body.r#type.as_ref().unwrap().as_str()))
+        || target_id == user_id.0
+    {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Check if user exists
+    if database
+        .query_one(
+            "SELECT * FROM users WHERE id = $1",
+            &[&Uuid::parse_str(target_id).unwrap()],
+        )
+        .await
+        .is_err()
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    if body.r#type.as_deref() != Some("blocked") {
+        return Ok(Json(
+            request_friend(sse_clients, database, &user_id.0, target_id).await?,
+        ));
+    }
+
+    // Block user, removing their side unless they blocked back
+    set_relationship(database, &user_id.0, target_id, "blocked").await?;
+    database
+        .execute(
+            "DELETE FROM relationships WHERE owner = $1 AND target = $2 AND type != 'blocked'",
+            &[&target_id, &user_id.0],
+        )
+        .await?;
+
+    Ok(Json(
+        broadcast_relationship(sse_clients, database, &user_id.0, target_id).await?,
+    ))
+}
+
+#[delete("/users/@me/relationships/<target_id>", format = "json")]
+async fn del_relationship(
+    target_id: &str,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    let pre_type = get_relationship_type(database, &user_id.0, target_id).await?;
+
+    if pre_type.is_none() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Remove friend, cancel or decline request, or unblock
+    database
+        .execute(
+            "DELETE FROM relationships WHERE owner = $1 AND target = $2",
+            &[&user_id.0, &target_id],
+        )
+        .await?;
+
+    if pre_type.unwrap() != "blocked" {
+        database
+            .execute(
+                "DELETE FROM relationships WHERE owner = $1 AND target = $2 AND type != 'blocked'",
+                &[&target_id, &user_id.0],
+            )
+            .await?;
+    }
+
+    broadcast_relationship(sse_clients, database, &user_id.0, target_id).await?;
+
+    Ok(Json(HashMap::new()))
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![
+        get_relationships,
+        add_friend,
+        get_relationship,
+        put_relationship,
+        del_relationship
+    ]
+}
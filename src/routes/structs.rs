@@ -16,7 +16,11 @@ You should have received a copy of the GNU Affero General Public License
 along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use rocket::serde::{Deserialize, Serialize};
+use rocket::{
+    fs::TempFile,
+    serde::{json::Value, Deserialize, Serialize},
+};
+use schemars::JsonSchema;
 
 /* account.rs */
 
@@ -66,7 +70,7 @@ pub struct ResetBody {
 
 /* GET /users/@me || PATCH /users/@me */
 /* response */
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(crate = "rocket::serde")]
 pub struct ReturnedUserMe {
     pub id: String,
@@ -94,7 +98,7 @@ pub struct PatchMeBody {
 
 /* GET /users/@me/guilds */
 /* response */
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(crate = "rocket::serde")]
 pub struct ReturnedGuild {
     pub id: String,
@@ -107,10 +111,28 @@ pub struct ReturnedGuild {
     pub creation: i64,
 }
 
-/* GET /users/<user_id> */
 /* response */
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(crate = "rocket::serde")]
+pub struct ReturnedMyGuild {
+    #[serde(flatten)]
+    pub guild: ReturnedGuild,
+    pub read_states: Vec<ReadState>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ReadState {
+    pub channel_id: String,
+    pub last_message_id: Option<String>,
+    pub unread_count: i64,
+    pub mention_count: i64,
+}
+
+/* GET /users/<user_id> */
+/* response */
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde")]
 pub struct ReturnedUser {
     pub id: String,
     pub username: String,
@@ -118,6 +140,27 @@ pub struct ReturnedUser {
     pub avatar: Option<String>,
     pub about: Option<String>,
     pub creation: i64,
+    pub bot: bool,
+}
+
+/* PATCH /users/@me/presence */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PatchPresenceBody {
+    pub status: Option<String>,
+    pub custom_status: Option<String>,
+    // Unix timestamp (in seconds) when the custom status is cleared, must be in the future
+    pub custom_status_expiration: Option<i64>,
+}
+/* response */
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ReturnedPresence {
+    pub user_id: String,
+    pub status: String,
+    pub custom_status: Option<String>,
+    pub custom_status_expiration: Option<i64>,
 }
 
 /* POST /users/@me/otp */
@@ -178,7 +221,7 @@ pub struct Guild {
     pub invites: Vec<Invite>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(crate = "rocket::serde")]
 pub struct Role {
     pub id: String,
@@ -217,6 +260,19 @@ pub struct Channel {
     pub roles: Vec<ChannelRole>,
     pub messages: Vec<Message>,
     pub pins: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread: Option<Thread>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct Thread {
+    pub message_id: String,
+    pub author: String,
+    pub auto_archive: i64,
+    pub archived: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -226,7 +282,7 @@ pub struct ChannelRole {
     pub permissions: i64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(crate = "rocket::serde")]
 pub struct Message {
     pub id: String,
@@ -237,6 +293,63 @@ pub struct Message {
     pub r#type: String,
     pub atachment: Option<String>,
     pub atachment_id: Option<String>,
+    #[serde(default)]
+    pub embeds: Vec<Embed>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<MessageWebhook>,
+    #[serde(default)]
+    pub mentions: Vec<String>,
+    #[serde(default)]
+    pub mention_roles: Vec<String>,
+    #[serde(default)]
+    pub mention_everyone: bool,
+    #[serde(default)]
+    pub reactions: Vec<MessageReaction>,
+    #[serde(default)]
+    pub emojis: Vec<MessageEmoji>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<MessageReference>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct MessageReference {
+    pub message_id: String,
+    pub author: String,
+    pub preview: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct MessageEmoji {
+    pub id: String,
+    pub name: String,
+    pub image: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct MessageReaction {
+    pub emoji: String,
+    pub count: i64,
+    pub me: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct MessageWebhook {
+    pub id: String,
+    pub username: String,
+    pub avatar: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct Embed {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
 }
 
 /* invites.rs */
@@ -261,3 +374,531 @@ pub struct CreateInviteBody {
     pub expiration: i64,
     pub max_uses: u64,
 }
+
+/* messages.rs || dms.rs */
+
+/* POST /guilds/<guild_id>/channels/<channel_id>/messages || POST /channels/<channel_id>/messages */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct CreateMessageBody {
+    pub content: String,
+    pub reply_to: Option<String>,
+    pub mention_author: Option<bool>,
+}
+
+/* PATCH /guilds/<guild_id>/channels/<channel_id>/messages/<message_id> || PATCH /channels/<channel_id>/messages/<message_id> */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct EditMessageBody {
+    pub content: String,
+}
+
+/* GET /guilds/<guild_id>/channels/<channel_id>/messages/<message_id>/revisions */
+/* response */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct MessageRevision {
+    pub content: String,
+    pub creation: i64,
+}
+
+/* POST /guilds/<guild_id>/channels/<channel_id>/messages/bulk-delete */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct BulkDeleteMessagesBody {
+    pub messages: Option<Vec<String>>,
+    pub author: Option<String>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+}
+
+/* GET /guilds/<guild_id>/channels/<channel_id>/messages/deleted */
+/* response */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct DeletedMessage {
+    #[serde(flatten)]
+    pub message: Message,
+    pub deleted: i64,
+    pub deleted_by: String,
+}
+
+/* GET /guilds/<guild_id>/messages/search */
+/* query */
+#[derive(FromForm, Debug)]
+pub struct SearchMessagesQuery {
+    pub q: Option<String>,
+    pub author: Option<String>,
+    pub channel: Option<String>,
+    pub has_attachment: Option<bool>,
+    pub has_link: Option<bool>,
+    pub mentions: Option<String>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub before: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/* response */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct MessageSearchResult {
+    pub channel_id: String,
+    pub message: Message,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+/* applications.rs */
+
+/* POST /applications */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct CreateApplicationBody {
+    pub username: String,
+}
+
+/* response */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ReturnedApplication {
+    pub id: String,
+    pub owner: String,
+    pub bot: ReturnedUser,
+    pub interactions_url: Option<String>,
+    pub redirect_uris: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interactions_secret: Option<String>,
+}
+
+/* PATCH /applications/<application_id> */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PatchApplicationBody {
+    pub interactions_url: Option<String>,
+    pub redirect_uris: Option<Vec<String>>,
+}
+
+/* POST /applications/<application_id>/authorize */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct AuthorizeApplicationBody {
+    pub guild_id: String,
+}
+
+/* attachments.rs */
+
+/* POST /guilds/<guild_id>/channels/<channel_id>/attachments || POST /channels/<channel_id>/attachments */
+/* body */
+#[derive(FromForm, Debug)]
+pub struct UploadAttachmentForm<'r> {
+    pub file: TempFile<'r>,
+    pub content: Option<String>,
+}
+
+/* dms.rs */
+
+/* POST /users/@me/channels */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct CreateDMBody {
+    pub recipients: Vec<String>,
+    pub name: Option<String>,
+}
+
+/* GET /users/@me/channels || POST /users/@me/channels */
+/* response */
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ReturnedDM {
+    pub id: String,
+    pub r#type: String,
+    pub name: Option<String>,
+    pub owner: Option<String>,
+    pub recipients: Vec<ReturnedUser>,
+    pub creation: i64,
+}
+
+/* relationships.rs */
+
+/* GET /users/@me/relationships || GET/PUT /users/@me/relationships/<user_id> */
+/* response */
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ReturnedRelationship {
+    pub id: String,
+    pub r#type: String,
+    pub user: ReturnedUser,
+    pub creation: Option<i64>,
+}
+
+/* POST /users/@me/relationships */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct AddFriendBody {
+    pub username: String,
+    pub discriminator: String,
+}
+
+/* PUT /users/@me/relationships/<user_id> */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PutRelationshipBody {
+    pub r#type: Option<String>,
+}
+
+/* subscriptions.rs */
+
+/* POST /guilds/<guild_id>/subscriptions */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct CreateSubscriptionBody {
+    pub url: String,
+    pub events: Vec<String>,
+}
+
+/* response */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ReturnedSubscription {
+    pub id: String,
+    pub guild_id: String,
+    pub url: String,
+    pub events: Vec<String>,
+    pub enabled: bool,
+    pub failures: i32,
+    pub creation: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+/* PATCH /guilds/<guild_id>/subscriptions/<subscription_id> */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PatchSubscriptionBody {
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+}
+
+/* GET /guilds/<guild_id>/subscriptions/<subscription_id>/deliveries */
+/* response */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ReturnedDelivery {
+    pub id: String,
+    pub delivery: String,
+    pub event: String,
+    pub attempt: i32,
+    pub status: Option<i32>,
+    pub success: bool,
+    pub error: Option<String>,
+    pub creation: i64,
+}
+
+/* webhooks.rs */
+
+/* POST /guilds/<guild_id>/channels/<channel_id>/webhooks */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct CreateWebhookBody {
+    pub name: String,
+    pub avatar: Option<String>,
+}
+
+/* response */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ReturnedWebhook {
+    pub id: String,
+    pub guild_id: String,
+    pub channel_id: String,
+    pub name: String,
+    pub avatar: Option<String>,
+    pub creator: String,
+    pub creation: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+/* POST /webhooks/<webhook_id>/<secret> */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ExecuteWebhookBody {
+    pub content: String,
+    pub username: Option<String>,
+    pub avatar: Option<String>,
+}
+
+/* commands.rs */
+
+/* POST /applications/<application_id>/commands || POST /applications/<application_id>/guilds/<guild_id>/commands */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct CreateCommandBody {
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub options: Vec<CommandOption>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct CommandOption {
+    pub name: String,
+    pub description: String,
+    pub r#type: String,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/* response */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ReturnedCommand {
+    pub id: String,
+    pub application_id: String,
+    pub guild_id: Option<String>,
+    pub name: String,
+    pub description: String,
+    pub options: Vec<CommandOption>,
+    pub creation: i64,
+}
+
+/* interactions.rs */
+
+/* POST /guilds/<guild_id>/channels/<channel_id>/interactions */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct CreateInteractionBody {
+    pub command_id: String,
+    #[serde(default)]
+    pub options: Vec<InteractionOption>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct InteractionOption {
+    pub name: String,
+    pub value: Value,
+}
+
+/* response */
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct Interaction {
+    pub id: String,
+    pub application_id: String,
+    pub guild_id: String,
+    pub channel_id: String,
+    pub user_id: String,
+    pub command_id: String,
+    pub command_name: String,
+    pub options: Vec<InteractionOption>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    pub creation: i64,
+}
+
+/* POST /interactions/<interaction_id>/<token>/callback */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct InteractionCallbackBody {
+    pub r#type: String,
+    pub content: Option<String>,
+}
+
+/* oauth2.rs */
+
+/* GET /oauth2/authorize */
+/* response */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ReturnedAuthorization {
+    pub application: ReturnedUser,
+    pub scopes: Vec<String>,
+    pub consented: bool,
+}
+
+/* POST /oauth2/authorize */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct AuthorizeBody {
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub state: Option<String>,
+    pub code_challenge: String,
+    pub code_challenge_method: String,
+}
+
+/* response */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct AuthorizeResp {
+    pub redirect: String,
+}
+
+/* POST /oauth2/token */
+/* body */
+#[derive(FromForm, Debug)]
+pub struct TokenForm {
+    pub grant_type: String,
+    pub client_id: String,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    pub refresh_token: Option<String>,
+}
+
+/* response */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct TokenResp {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    pub refresh_token: String,
+    pub scope: String,
+}
+
+/* POST /oauth2/introspect || POST /oauth2/revoke */
+/* body */
+#[derive(FromForm, Debug)]
+pub struct TokenRequestForm {
+    pub token: String,
+    pub client_id: String,
+}
+
+/* POST /oauth2/introspect */
+/* response */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct IntrospectResp {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+}
+
+/* GET /users/@me/oauth2/consents */
+/* response */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ReturnedConsent {
+    pub client_id: String,
+    pub scopes: Vec<String>,
+    pub creation: i64,
+}
+
+/* discovery.rs */
+
+/* GET /discovery/guilds */
+/* response */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct DiscoverableGuild {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    pub members: usize,
+    pub creation: i64,
+}
+
+/* emojis.rs */
+
+/* GET /guilds/<guild_id>/emojis */
+/* response */
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ReturnedEmoji {
+    pub id: String,
+    pub name: String,
+    pub image: String,
+    pub author: String,
+    pub creation: i64,
+}
+
+/* PATCH /guilds/<guild_id>/emojis/<emoji_id> */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PatchEmojiBody {
+    pub name: String,
+}
+
+/* threads.rs */
+
+/* POST /guilds/<guild_id>/channels/<channel_id>/messages/<message_id>/threads */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct CreateThreadBody {
+    pub name: String,
+    pub auto_archive: Option<i64>,
+}
+
+/* response */
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ReturnedThread {
+    pub id: String,
+    pub parent_id: String,
+    pub message_id: String,
+    pub name: String,
+    pub author: String,
+    pub auto_archive: i64,
+    pub archived: bool,
+    pub last_activity: i64,
+    pub creation: i64,
+}
+
+/* PATCH /guilds/<guild_id>/channels/<channel_id>/threads/<thread_id> */
+/* body */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PatchThreadBody {
+    pub name: Option<String>,
+    pub auto_archive: Option<i64>,
+    pub archived: Option<bool>,
+}
+
+/* audit_log.rs */
+
+/* GET /guilds/<guild_id>/audit-log */
+/* response */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct AuditLogEntry {
+    pub id: String,
+    pub author: String,
+    pub action: String,
+    pub target: Option<String>,
+    pub details: Value,
+    pub creation: i64,
+}
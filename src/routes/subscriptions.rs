@@ -0,0 +1,318 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::structs::{
+    CreateSubscriptionBody, PatchSubscriptionBody, ReturnedDelivery, ReturnedSubscription,
+};
+use crate::{
+    utils::{
+        self,
+        permissions::{check_guild_permission, GuildPermissions},
+    },
+    AppError, Auth,
+};
+
+use rand::Rng;
+use rocket::{http::Status, serde::json::Json, Route, State};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+// Maximum amount of subscriptions per guild
+const MAX_SUBSCRIPTIONS: usize = 10;
+// Amount of deliveries returned by default, and at most
+const DEFAULT_DELIVERIES_LIMIT: i64 = 50;
+const MAX_DELIVERIES_LIMIT: i64 = 100;
+
+fn get_returned_subscription(subscription: &Row, secret: Option<String>) -> ReturnedSubscription {
+    ReturnedSubscription {
+        id: subscription.get::<&str, Uuid>("id").to_string(),
+        guild_id: subscription.get::<&str, Uuid>("guild").to_string(),
+        url: subscription.get::<&str, String>("url"),
+        events: subscription.get::<&str, Vec<String>>("events"),
+        enabled: subscription.get::<&str, bool>("enabled"),
+        failures: subscription.get::<&str, i32>("failures"),
+        creation: subscription.get::<&str, i64>("creation"),
+        secret,
+    }
+}
+
+// Check if the user can manage the guild's subscriptions
+async fn check_manage_guild(
+    database: &tokio_postgres::Client,
+    guild_id: &str,
+    user_id: &str,
+) -> Result<(), AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if can manage the guild
+    if !check_guild_permission(
+        &pre_guild.unwrap(),
+        &user_id.to_string(),
+        GuildPermissions::MANAGE_GUILD,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    Ok(())
+}
+
+#[get("/guilds/<guild_id>/subscriptions", format = "json")]
+async fn get_subscriptions(
+    guild_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<ReturnedSubscription>>, AppError> {
+    check_manage_guild(database, guild_id, &user_id.0).await?;
+
+    let subscriptions = database
+        .query(
+            "SELECT * FROM subscriptions WHERE guild = $1 ORDER BY creation",
+            &[&Uuid::parse_str(guild_id).unwrap()],
+        )
+        .await?;
+
+    Ok(Json(
+        subscriptions
+            .iter()
+            .map(|subscription| get_returned_subscription(subscription, None))
+            .collect(),
+    ))
+}
+
+#[post("/guilds/<guild_id>/subscriptions", format = "json", data = "<body>")]
+async fn create_subscription(
+    guild_id: &str,
+    body: Json<CreateSubscriptionBody>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedSubscription>, AppError> {
+    // Check if URL and events are valid
+    if !utils::network::is_allowed_url(&body.url)
+        || !utils::subscriptions::are_subscribable(&body.events)
+    {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    check_manage_guild(database, guild_id, &user_id.0).await?;
+
+    // Check if the guild has too many subscriptions
+    if database
+        .query(
+            "SELECT id FROM subscriptions WHERE guild = $1",
+            &[&Uuid::parse_str(guild_id).unwrap()],
+        )
+        .await?
+        .len()
+        >= MAX_SUBSCRIPTIONS
+    {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Create subscription
+    let secret = hex::encode(rand::rng().random::<[u8; 32]>());
+    let subscription = database.query_one("INSERT INTO subscriptions (id, guild, url, secret, events, enabled, failures, creation) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING *",
+    &[
+        &Uuid::new_v4(),
+        &Uuid::parse_str(guild_id).unwrap(),
+        &body.url,
+        &secret,
+        &body.events,
+        &true,
+        &0,
+        &(SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64),
+    ]).await?;
+
+    Ok(Json(get_returned_subscription(&subscription, Some(secret))))
+}
+
+#[patch(
+    "/guilds/<guild_id>/subscriptions/<subscription_id>",
+    format = "json",
+    data = "<body>"
+)]
+async fn patch_subscription(
+    guild_id: &str,
+    subscription_id: &str,
+    body: Json<PatchSubscriptionBody>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedSubscription>, AppError> {
+    // Check if URL and events are valid
+    if body
+        .url
+        .as_ref()
+        .is_some_and(|url| !utils::network::is_allowed_url(url))
+        || body
+            .events
+            .as_ref()
+            .is_some_and(|events| !utils::subscriptions::are_subscribable(events))
+    {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    check_manage_guild(database, guild_id, &user_id.0).await?;
+
+    // Re-enabling a subscription resets its failures
+    let pre_subscription = database
+        .query_one(
+            "UPDATE subscriptions SET url = coalesce($1, url), events = coalesce($2, events),
+                enabled = coalesce($3, enabled),
+                failures = CASE WHEN $3 THEN 0 ELSE failures END
+            WHERE id = $4 AND guild = $5 RETURNING *",
+            &[
+                &body.url,
+                &body.events,
+                &body.enabled,
+                &Uuid::parse_str(subscription_id).unwrap(),
+                &Uuid::parse_str(guild_id).unwrap(),
+            ],
+        )
+        .await;
+
+    if pre_subscription.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    Ok(Json(get_returned_subscription(
+        &pre_subscription.unwrap(),
+        None,
+    )))
+}
+
+#[delete("/guilds/<guild_id>/subscriptions/<subscription_id>", format = "json")]
+async fn del_subscription(
+    guild_id: &str,
+    subscription_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    check_manage_guild(database, guild_id, &user_id.0).await?;
+
+    // Delete subscription
+    if database
+        .execute(
+            "DELETE FROM subscriptions WHERE id = $1 AND guild = $2",
+            &[
+                &Uuid::parse_str(subscription_id).unwrap(),
+                &Uuid::parse_str(guild_id).unwrap(),
+            ],
+        )
+        .await?
+        < 1
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    database
+        .execute(
+            "DELETE FROM subscription_deliveries WHERE subscription = $1",
+            &[&Uuid::parse_str(subscription_id).unwrap()],
+        )
+        .await?;
+
+    Ok(Json(HashMap::new()))
+}
+
+#[get(
+    "/guilds/<guild_id>/subscriptions/<subscription_id>/deliveries?<limit>",
+    format = "json"
+)]
+async fn get_subscription_deliveries(
+    guild_id: &str,
+    subscription_id: &str,
+    limit: Option<i64>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<ReturnedDelivery>>, AppError> {
+    check_manage_guild(database, guild_id, &user_id.0).await?;
+
+    // Check if subscription exists
+    if database
+        .query_one(
+            "SELECT * FROM subscriptions WHERE id = $1 AND guild = $2",
+            &[
+                &Uuid::parse_str(subscription_id).unwrap(),
+                &Uuid::parse_str(guild_id).unwrap(),
+            ],
+        )
+        .await
+        .is_err()
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let deliveries = database
+        .query(
+            "SELECT * FROM subscription_deliveries WHERE subscription = $1 ORDER BY creation DESC, attempt DESC LIMIT $2",
+            &[
+                &Uuid::parse_str(subscription_id).unwrap(),
+                &limit
+                    .unwrap_or(DEFAULT_DELIVERIES_LIMIT)
+                    .clamp(1, MAX_DELIVERIES_LIMIT),
+            ],
+        )
+        .await?;
+
+    Ok(Json(
+        deliveries
+            .iter()
+            .map(|delivery| ReturnedDelivery {
+                id: delivery.get::<&str, Uuid>("id").to_string(),
+                delivery: delivery.get::<&str, Uuid>("delivery").to_string(),
+                event: delivery.get::<&str, String>("event"),
+                attempt: delivery.get::<&str, i32>("attempt"),
+                status: delivery.get::<&str, Option<i32>>("status"),
+                success: delivery.get::<&str, bool>("success"),
+                error: delivery.get::<&str, Option<String>>("error"),
+                creation: delivery.get::<&str, i64>("creation"),
+            })
+            .collect(),
+    ))
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![
+        get_subscriptions,
+        create_subscription,
+        patch_subscription,
+        del_subscription,
+        get_subscription_deliveries
+    ]
+}
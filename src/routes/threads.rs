@@ -0,0 +1,446 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::structs::{Channel, CreateThreadBody, Member, PatchThreadBody, ReturnedThread, Thread};
+use crate::{
+    utils::{
+        self,
+        permissions::{check_channel_permission, ChannelPermissions},
+    },
+    AppError, Auth,
+};
+
+use rocket::{
+    http::Status,
+    serde::json::{from_value, to_value, Json, Value},
+    Route, State,
+};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+// Seconds of inactivity after which a thread is archived
+const AUTO_ARCHIVE_DURATIONS: [i64; 4] =
+    [60 * 60, 24 * 60 * 60, 3 * 24 * 60 * 60, 7 * 24 * 60 * 60];
+const DEFAULT_AUTO_ARCHIVE: i64 = 24 * 60 * 60;
+// Maximum length of a thread's name
+const MAX_NAME_LENGTH: usize = 100;
+
+fn returned_thread(channel: &Channel, last_activity: i64) -> ReturnedThread {
+    let thread = channel.thread.as_ref().unwrap();
+
+    ReturnedThread {
+        id: channel.id.clone(),
+        parent_id: channel.parent.clone().unwrap(),
+        message_id: thread.message_id.clone(),
+        name: channel.name.clone(),
+        author: thread.author.clone(),
+        auto_archive: thread.auto_archive,
+        // Inactive threads are archived until someone posts in them
+        archived: thread.archived
+            || SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64
+                - last_activity
+                > thread.auto_archive,
+        last_activity,
+        creation: channel.creation,
+    }
+}
+
+// Get the time of the last message in every thread, or its creation
+async fn get_last_activities(
+    database: &tokio_postgres::Client,
+    threads: &[&Channel],
+) -> Result<HashMap<String, i64>, AppError> {
+    let ids: Vec<Uuid> = threads
+        .iter()
+        .map(|thread| Uuid::parse_str(&thread.id).unwrap())
+        .collect();
+
+    let activities = database
+        .query(
+            "SELECT channel, max(creation) AS last_activity FROM messages
+            WHERE channel = any($1) AND deleted IS NULL GROUP BY channel",
+            &[&ids],
+        )
+        .await?;
+
+    Ok(threads
+        .iter()
+        .map(|thread| {
+            let last_activity = activities
+                .iter()
+                .find(|activity| activity.get::<&str, Uuid>("channel").to_string() == thread.id)
+                .map(|activity| activity.get::<&str, i64>("last_activity"))
+                .unwrap_or(thread.creation);
+
+            (thread.id.clone(), last_activity)
+        })
+        .collect())
+}
+
+// Broadcast threadCreated or threadUpdated event to every member that can view the thread
+async fn broadcast_thread(
+    sse_clients: &crate::SSEClients,
+    guild: &Row,
+    guild_id: &str,
+    thread: &ReturnedThread,
+    created: bool,
+) {
+    let members: Vec<Member> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("members"))).unwrap();
+
+    for member in members.iter().filter(|member| {
+        check_channel_permission(
+            guild,
+            &thread.parent_id,
+            &member.id,
+            ChannelPermissions::VIEW_CHANNEL,
+        )
+    }) {
+        utils::sse::broadcast(
+            sse_clients,
+            &member.id,
+            if created {
+                utils::structs::SSEEvent::ThreadCreated { guild_id, thread }
+            } else {
+                utils::structs::SSEEvent::ThreadUpdated { guild_id, thread }
+            },
+        )
+        .await;
+    }
+}
+
+#[post(
+    "/guilds/<guild_id>/channels/<channel_id>/messages/<message_id>/threads",
+    format = "json",
+    data = "<body>"
+)]
+async fn create_thread(
+    guild_id: &str,
+    channel_id: &str,
+    message_id: &str,
+    body: Json<CreateThreadBody>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedThread>, AppError> {
+    let auto_archive = body.auto_archive.unwrap_or(DEFAULT_AUTO_ARCHIVE);
+
+    // Check if name and auto archive duration are valid
+    if body.name.trim().is_empty()
+        || body.name.chars().count() > MAX_NAME_LENGTH
+        || !AUTO_ARCHIVE_DURATIONS.contains(&auto_archive)
+    {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+    let pre_channel = channels.iter().find(|channel| channel.id == channel_id);
+
+    if pre_channel.is_none() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if the channel isn't a thread itself
+    if pre_channel.unwrap().parent.is_some() {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Check if can send messages
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::SEND_MESSAGES,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Check if the message exists in the channel
+    if database
+        .query_one(
+            "SELECT * FROM messages WHERE id = $1 AND channel = $2 AND deleted IS NULL",
+            &[
+                &Uuid::parse_str(message_id).unwrap(),
+                &Uuid::parse_str(channel_id).unwrap(),
+            ],
+        )
+        .await
+        .is_err()
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if the message already has a thread
+    if channels.iter().any(|channel| {
+        channel
+            .thread
+            .as_ref()
+            .is_some_and(|thread| thread.message_id == message_id)
+    }) {
+        return Err(AppError(Status::Conflict));
+    }
+
+    let channel = Channel {
+        id: Uuid::new_v4().to_string(),
+        name: body.name.trim().to_string(),
+        topic: None,
+        r#type: "thread".to_string(),
+        creation: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64,
+        roles: vec![],
+        messages: vec![],
+        pins: vec![],
+        parent: Some(channel_id.to_string()),
+        thread: Some(Thread {
+            message_id: message_id.to_string(),
+            author: user_id.0,
+            auto_archive,
+            archived: false,
+        }),
+    };
+
+    // Append thread
+    database
+        .execute(
+            "UPDATE guilds SET channels = array_append(channels, $1) WHERE id = $2",
+            &[
+                &to_value(&channel).unwrap(),
+                &Uuid::parse_str(guild_id).unwrap(),
+            ],
+        )
+        .await?;
+
+    let thread = returned_thread(&channel, channel.creation);
+
+    broadcast_thread(sse_clients, &guild, guild_id, &thread, true).await;
+
+    Ok(Json(thread))
+}
+
+#[get("/guilds/<guild_id>/channels/<channel_id>/threads", format = "json")]
+async fn get_threads(
+    guild_id: &str,
+    channel_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<ReturnedThread>>, AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    if !channels.iter().any(|channel| channel.id == channel_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if can view the channel
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::VIEW_CHANNEL,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    let threads: Vec<&Channel> = channels
+        .iter()
+        .filter(|channel| channel.parent.as_deref() == Some(channel_id))
+        .collect();
+    let activities = get_last_activities(database, &threads).await?;
+
+    // Most recently active first
+    let mut returned_threads: Vec<ReturnedThread> = threads
+        .iter()
+        .map(|thread| returned_thread(thread, activities[&thread.id]))
+        .collect();
+    returned_threads.sort_by_key(|thread| std::cmp::Reverse(thread.last_activity));
+
+    Ok(Json(returned_threads))
+}
+
+#[patch(
+    "/guilds/<guild_id>/channels/<channel_id>/threads/<thread_id>",
+    format = "json",
+    data = "<body>"
+)]
+#[allow(clippy::too_many_arguments)]
+async fn patch_thread(
+    guild_id: &str,
+    channel_id: &str,
+    thread_id: &str,
+    body: Json<PatchThreadBody>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedThread>, AppError> {
+    // Check if name and auto archive duration are valid
+    if body
+        .name
+        .as_ref()
+        .is_some_and(|name| name.trim().is_empty() || name.chars().count() > MAX_NAME_LENGTH)
+        || body
+            .auto_archive
+            .is_some_and(|auto_archive| !AUTO_ARCHIVE_DURATIONS.contains(&auto_archive))
+    {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id.0],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the thread exists in the channel
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+    let pre_thread = channels
+        .into_iter()
+        .find(|channel| channel.id == thread_id && channel.parent.as_deref() == Some(channel_id));
+
+    if pre_thread.is_none() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let mut channel = pre_thread.unwrap();
+
+    // Check if can view the thread
+    if !check_channel_permission(
+        &guild,
+        &thread_id.to_string(),
+        &user_id.0,
+        ChannelPermissions::VIEW_CHANNEL,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Check if the author of the thread, or can manage the channel
+    if channel.thread.as_ref().unwrap().author != user_id.0
+        && !check_channel_permission(
+            &guild,
+            &thread_id.to_string(),
+            &user_id.0,
+            ChannelPermissions::MANAGE_CHANNEL,
+        )
+    {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    if let Some(name) = &body.name {
+        channel.name = name.trim().to_string();
+    }
+
+    let thread = channel.thread.as_mut().unwrap();
+    if let Some(auto_archive) = body.auto_archive {
+        thread.auto_archive = auto_archive;
+    }
+    if let Some(archived) = body.archived {
+        thread.archived = archived;
+    }
+
+    // Replace thread
+    database
+        .execute(
+            "UPDATE guilds SET channels = array_replace(channels,
+                                    (
+                                        SELECT channel
+                                        FROM unnest(channels) AS channel
+                                        WHERE channel->>'id' = $1
+                                    ),
+                                    $2
+                                )
+            WHERE id = $3",
+            &[
+                &thread_id,
+                &to_value(&channel).unwrap(),
+                &Uuid::parse_str(guild_id).unwrap(),
+            ],
+        )
+        .await?;
+
+    let activities = get_last_activities(database, &[&channel]).await?;
+    let thread = returned_thread(&channel, activities[&channel.id]);
+
+    broadcast_thread(sse_clients, &guild, guild_id, &thread, false).await;
+
+    Ok(Json(thread))
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![create_thread, get_threads, patch_thread]
+}
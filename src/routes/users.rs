@@ -17,25 +17,39 @@ along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
 */
 
 use super::structs::{
-    PatchMeBody, ReturnedGuild, ReturnedOtp, ReturnedUser, ReturnedUserMe, SetupOTPBody,
+    Channel, PatchMeBody, PatchPresenceBody, ReturnedGuild, ReturnedMyGuild, ReturnedOtp,
+    ReturnedPresence, ReturnedUser, ReturnedUserMe, SetupOTPBody,
+};
+use crate::{
+    utils::{
+        self,
+        images::MAX_IMAGE_SIZE,
+        permissions::{check_channel_permission, ChannelPermissions},
+        presence::{Presence, Presences},
+        storage::StorageBackend,
+    },
+    AppError, Auth,
 };
-use crate::{utils, AppError, Auth};
 
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2,
 };
 use rocket::{
+    data::{Data, ToByteUnit},
     http::Status,
     serde::json::{serde_json, Json, Value},
     Route, State,
 };
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
 use totp_rs::{Algorithm, Secret, TOTP};
 
 #[get("/users/@me", format = "json")]
 async fn get_me(
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
 ) -> Result<Json<ReturnedUserMe>, AppError> {
     // Get user
@@ -64,7 +78,7 @@ async fn get_me(
 
 #[delete("/users/@me", format = "json")]
 async fn del_me(
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
 ) -> Result<Json<HashMap<String, String>>, AppError> {
     // Delete user
@@ -86,7 +100,7 @@ async fn del_me(
 async fn patch_me(
     body: Json<PatchMeBody>,
     sse_clients: &State<crate::SSEClients>,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
 ) -> Result<Json<ReturnedUserMe>, AppError> {
     // Check if username is too long
@@ -208,22 +222,166 @@ async fn patch_me(
     utils::sse::broadcast(
         sse_clients,
         &user_id.0,
-        utils::structs::SSEEvent {
-            event: "userEdited",
-            user: Some(&final_user),
-            ..Default::default()
-        },
+        utils::structs::SSEEvent::UserEdited { user: &final_user },
     )
     .await;
 
     Ok(Json(final_user))
 }
 
+#[put("/users/@me/avatar", data = "<body>")]
+async fn put_avatar(
+    body: Data<'_>,
+    storage: &State<StorageBackend>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedUserMe>, AppError> {
+    let data = body.open(MAX_IMAGE_SIZE.bytes()).into_bytes().await?;
+
+    // Check if image is too large
+    if !data.is_complete() {
+        return Err(AppError(Status::PayloadTooLarge));
+    }
+
+    let avatar = utils::images::store_image(storage, "avatars", data.into_inner()).await?;
+
+    // Update user
+    let user = database
+        .query_one(
+            "UPDATE users SET avatar = $1 WHERE id = $2 RETURNING *",
+            &[&avatar, &uuid::Uuid::parse_str(&user_id.0).unwrap()],
+        )
+        .await?;
+
+    let final_user = ReturnedUserMe {
+        id: user.get::<&str, uuid::Uuid>("id").to_string(),
+        email: user.get::<&str, String>("email"),
+        username: user.get::<&str, String>("username"),
+        discriminator: user.get::<&str, String>("discriminator"),
+        avatar: Some(avatar),
+        about: user
+            .try_get::<&str, Option<String>>("about")
+            .unwrap_or(None),
+        tfa: user.try_get::<&str, String>("otp").is_ok(),
+        creation: user.get::<&str, i64>("creation"),
+    };
+
+    // Broadcast userEdited event
+    utils::sse::broadcast(
+        sse_clients,
+        &user_id.0,
+        utils::structs::SSEEvent::UserEdited { user: &final_user },
+    )
+    .await;
+
+    Ok(Json(final_user))
+}
+
+#[patch("/users/@me/presence", format = "json", data = "<body>")]
+async fn patch_presence(
+    body: Json<PatchPresenceBody>,
+    sse_clients: &State<crate::SSEClients>,
+    presences: &State<Presences>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedPresence>, AppError> {
+    // Check if status is valid
+    if body.status.is_some()
+        && !["online", "idle", "dnd", "invisible"].contains(&body.status.as_ref().unwrap().as_str())
+    {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Check if custom status is too long
+    if body.custom_status.is_some() && body.custom_status.as_ref().unwrap().len() > 128 {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+
+    // Check if custom status expiration is in the past
+    if body
+        .custom_status_expiration
+        .is_some_and(|expiration| expiration <= now)
+    {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Get user
+    let user = database
+        .query_one(
+            "SELECT * FROM users WHERE id = $1",
+            &[&uuid::Uuid::parse_str(&user_id.0).unwrap()],
+        )
+        .await?;
+
+    // Create final presence, an empty custom status clears it
+    let mut presence = Presence {
+        status: if let Some(status) = &body.status {
+            status.to_string()
+        } else {
+            user.try_get::<&str, Option<String>>("presence")
+                .unwrap_or(None)
+                .unwrap_or("online".to_string())
+        },
+        custom_status: if let Some(custom_status) = &body.custom_status {
+            Some(custom_status.to_string()).filter(|text| !text.is_empty())
+        } else {
+            user.try_get::<&str, Option<String>>("custom_status")
+                .unwrap_or(None)
+        },
+        custom_status_expiration: if body.custom_status_expiration.is_some() {
+            body.custom_status_expiration
+        } else if body.custom_status.is_some() {
+            None
+        } else {
+            user.try_get::<&str, Option<i64>>("custom_status_expiration")
+                .unwrap_or(None)
+        },
+    };
+
+    if presence.custom_status.is_none() {
+        presence.custom_status_expiration = None;
+    }
+
+    database
+        .execute(
+            "UPDATE users SET presence = $1, custom_status = $2, custom_status_expiration = $3 WHERE id = $4",
+            &[
+                &presence.status,
+                &presence.custom_status,
+                &presence.custom_status_expiration,
+                &uuid::Uuid::parse_str(&user_id.0).unwrap(),
+            ],
+        )
+        .await?;
+
+    // Broadcast presenceUpdated event if the user is connected
+    let mut presences_lock = presences.lock().await;
+
+    if presences_lock.contains_key(&user_id.0) {
+        presences_lock.insert(user_id.0.clone(), presence.clone());
+        drop(presences_lock);
+
+        utils::presence::broadcast(sse_clients, database, &user_id.0, Some(&presence)).await;
+    }
+
+    Ok(Json(utils::presence::returned_presence(
+        &user_id.0,
+        Some(&presence),
+        true,
+    )))
+}
+
 #[get("/users/@me/guilds", format = "json")]
 async fn get_my_guilds(
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
-) -> Result<Json<Vec<ReturnedGuild>>, AppError> {
+) -> Result<Json<Vec<ReturnedMyGuild>>, AppError> {
     // Get guilds
     let guilds = database
         .query(
@@ -237,9 +395,26 @@ async fn get_my_guilds(
         .await?;
 
     // Parse guilds
-    let mut returned_guilds: Vec<ReturnedGuild> = Vec::new();
+    let mut returned_guilds: Vec<ReturnedMyGuild> = Vec::new();
     for guild in guilds.iter() {
-        returned_guilds.push(ReturnedGuild {
+        // Get the read states of the channels the user can view
+        let channels: Vec<Channel> =
+            serde_json::from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels")))
+                .unwrap();
+        let viewable: Vec<uuid::Uuid> = channels
+            .iter()
+            .filter(|channel| {
+                check_channel_permission(
+                    guild,
+                    &channel.id,
+                    &user_id.0,
+                    ChannelPermissions::VIEW_CHANNEL,
+                )
+            })
+            .map(|channel| uuid::Uuid::parse_str(&channel.id).unwrap())
+            .collect();
+
+        let guild_returned = ReturnedGuild {
             id: guild.get::<&str, uuid::Uuid>("id").to_string(),
             name: guild.get::<&str, String>("name"),
             description: guild
@@ -253,6 +428,12 @@ async fn get_my_guilds(
                 .unwrap(),
             members: guild.get::<&str, Vec<Value>>("members").len(),
             creation: guild.get::<&str, i64>("creation"),
+        };
+
+        returned_guilds.push(ReturnedMyGuild {
+            guild: guild_returned,
+            read_states: utils::read_states::get_read_states(database, &user_id.0, &viewable)
+                .await?,
         })
     }
 
@@ -262,7 +443,7 @@ async fn get_my_guilds(
 #[get("/users/<user_id>", format = "json")]
 async fn get_user(
     user_id: &str,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     _user_id: Auth,
 ) -> Result<Json<ReturnedUser>, AppError> {
     // Get user
@@ -290,12 +471,13 @@ async fn get_user(
             .try_get::<&str, Option<String>>("about")
             .unwrap_or(None),
         creation: user.get::<&str, i64>("creation"),
+        bot: user.get::<&str, String>("type") == "BOT",
     }))
 }
 
 #[post("/users/@me/otp", format = "json")]
 async fn gen_otp_secret(
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
 ) -> Result<Json<ReturnedOtp>, AppError> {
     // Get user
@@ -334,7 +516,7 @@ async fn gen_otp_secret(
 async fn setup_otp(
     body: Json<SetupOTPBody>,
     secret: &str,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
 ) -> Result<Json<HashMap<String, String>>, AppError> {
     // Get user
@@ -379,7 +561,7 @@ async fn setup_otp(
 #[delete("/users/@me/otp", format = "json", data = "<body>")]
 async fn del_otp(
     body: Json<SetupOTPBody>,
-    database: &State<tokio_postgres::Client>,
+    database: &State<crate::Database>,
     user_id: Auth,
 ) -> Result<Json<HashMap<String, String>>, AppError> {
     // Get user
@@ -425,6 +607,8 @@ pub fn get_routes() -> Vec<Route> {
         get_me,
         del_me,
         patch_me,
+        put_avatar,
+        patch_presence,
         get_my_guilds,
         get_user,
         gen_otp_secret,
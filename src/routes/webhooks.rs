@@ -0,0 +1,346 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::structs::{
+    Channel, CreateWebhookBody, ExecuteWebhookBody, Message, MessageWebhook, ReturnedWebhook,
+};
+use crate::{
+    utils::{
+        self,
+        messages::Destination,
+        permissions::{check_channel_permission, ChannelPermissions},
+        subscriptions::Dispatcher,
+        unfurl::Unfurler,
+    },
+    AppError, Auth,
+};
+
+use rand::Rng;
+use reqwest::Url;
+use rocket::{
+    http::Status,
+    serde::json::{from_value, Json, Value},
+    Route, State,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+// Maximum amount of webhooks per channel
+const MAX_WEBHOOKS: usize = 10;
+// Maximum length of a webhook's name and avatar URL
+const MAX_NAME_LENGTH: usize = 30;
+const MAX_AVATAR_LENGTH: usize = 2048;
+
+fn get_returned_webhook(webhook: &Row, secret: Option<String>) -> ReturnedWebhook {
+    ReturnedWebhook {
+        id: webhook.get::<&str, Uuid>("id").to_string(),
+        guild_id: webhook.get::<&str, Uuid>("guild").to_string(),
+        channel_id: webhook.get::<&str, Uuid>("channel").to_string(),
+        name: webhook.get::<&str, String>("name"),
+        avatar: webhook.get::<&str, Option<String>>("avatar"),
+        creator: webhook.get::<&str, String>("creator"),
+        creation: webhook.get::<&str, i64>("creation"),
+        secret,
+    }
+}
+
+// Check if the name is shown properly
+fn is_valid_name(name: &str) -> bool {
+    !name.trim().is_empty() && name.len() <= MAX_NAME_LENGTH
+}
+
+// Check if the avatar is an HTTP URL
+fn is_valid_avatar(avatar: &str) -> bool {
+    avatar.len() <= MAX_AVATAR_LENGTH
+        && Url::parse(avatar).is_ok_and(|url| ["http", "https"].contains(&url.scheme()))
+}
+
+fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+// Check if the user can manage the channel's webhooks
+async fn check_manage_channel(
+    database: &tokio_postgres::Client,
+    guild_id: &str,
+    channel_id: &str,
+    user_id: &str,
+) -> Result<(), AppError> {
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1 AND EXISTS (
+               SELECT 1
+               FROM unnest(members) AS member
+               WHERE member->>'id' = $2
+           )",
+            &[&Uuid::parse_str(guild_id).unwrap(), &user_id],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    if !channels.iter().any(|channel| channel.id == channel_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    // Check if can manage the channel
+    if !check_channel_permission(
+        &guild,
+        &channel_id.to_string(),
+        &user_id.to_string(),
+        ChannelPermissions::MANAGE_CHANNEL,
+    ) {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    Ok(())
+}
+
+#[get("/guilds/<guild_id>/channels/<channel_id>/webhooks", format = "json")]
+async fn get_webhooks(
+    guild_id: &str,
+    channel_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<Vec<ReturnedWebhook>>, AppError> {
+    check_manage_channel(database, guild_id, channel_id, &user_id.0).await?;
+
+    let webhooks = database
+        .query(
+            "SELECT * FROM webhooks WHERE channel = $1 ORDER BY creation",
+            &[&Uuid::parse_str(channel_id).unwrap()],
+        )
+        .await?;
+
+    Ok(Json(
+        webhooks
+            .iter()
+            .map(|webhook| get_returned_webhook(webhook, None))
+            .collect(),
+    ))
+}
+
+#[post(
+    "/guilds/<guild_id>/channels/<channel_id>/webhooks",
+    format = "json",
+    data = "<body>"
+)]
+async fn create_webhook(
+    guild_id: &str,
+    channel_id: &str,
+    body: Json<CreateWebhookBody>,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<ReturnedWebhook>, AppError> {
+    // Check if name and avatar are valid
+    if !is_valid_name(&body.name)
+        || body
+            .avatar
+            .as_ref()
+            .is_some_and(|avatar| !is_valid_avatar(avatar))
+    {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    check_manage_channel(database, guild_id, channel_id, &user_id.0).await?;
+
+    // Check if the channel has too many webhooks
+    if database
+        .query(
+            "SELECT id FROM webhooks WHERE channel = $1",
+            &[&Uuid::parse_str(channel_id).unwrap()],
+        )
+        .await?
+        .len()
+        >= MAX_WEBHOOKS
+    {
+        return Err(AppError(Status::Forbidden));
+    }
+
+    // Create webhook, only the secret's hash is kept
+    let secret = hex::encode(rand::rng().random::<[u8; 32]>());
+    let webhook = database.query_one("INSERT INTO webhooks (id, guild, channel, name, avatar, secret, creator, creation) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING *",
+    &[
+        &Uuid::new_v4(),
+        &Uuid::parse_str(guild_id).unwrap(),
+        &Uuid::parse_str(channel_id).unwrap(),
+        &body.name,
+        &body.avatar,
+        &hash_secret(&secret),
+        &user_id.0,
+        &(SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64),
+    ]).await?;
+
+    Ok(Json(get_returned_webhook(&webhook, Some(secret))))
+}
+
+#[delete(
+    "/guilds/<guild_id>/channels/<channel_id>/webhooks/<webhook_id>",
+    format = "json"
+)]
+async fn del_webhook(
+    guild_id: &str,
+    channel_id: &str,
+    webhook_id: &str,
+    database: &State<crate::Database>,
+    user_id: Auth,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    check_manage_channel(database, guild_id, channel_id, &user_id.0).await?;
+
+    // Delete webhook
+    if database
+        .execute(
+            "DELETE FROM webhooks WHERE id = $1 AND channel = $2",
+            &[
+                &Uuid::parse_str(webhook_id).unwrap(),
+                &Uuid::parse_str(channel_id).unwrap(),
+            ],
+        )
+        .await?
+        < 1
+    {
+        return Err(AppError(Status::NotFound));
+    }
+
+    Ok(Json(HashMap::new()))
+}
+
+#[post("/webhooks/<webhook_id>/<secret>", format = "json", data = "<body>")]
+async fn execute_webhook(
+    webhook_id: &str,
+    secret: &str,
+    body: Json<ExecuteWebhookBody>,
+    unfurler: &State<Unfurler>,
+    dispatcher: &State<Dispatcher>,
+    sse_clients: &State<crate::SSEClients>,
+    database: &State<crate::Database>,
+) -> Result<Json<Message>, AppError> {
+    // Check if content, name and avatar are valid
+    if body.content.trim().is_empty()
+        || body.content.len() > utils::messages::MAX_CONTENT_LENGTH
+        || body
+            .username
+            .as_ref()
+            .is_some_and(|username| !is_valid_name(username))
+        || body
+            .avatar
+            .as_ref()
+            .is_some_and(|avatar| !is_valid_avatar(avatar))
+    {
+        return Err(AppError(Status::BadRequest));
+    }
+
+    // Get webhook
+    let pre_webhook = database
+        .query_one(
+            "SELECT * FROM webhooks WHERE id = $1 AND secret = $2",
+            &[&Uuid::parse_str(webhook_id).unwrap(), &hash_secret(secret)],
+        )
+        .await;
+
+    if pre_webhook.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let webhook = pre_webhook.unwrap();
+    let guild_id = webhook.get::<&str, Uuid>("guild").to_string();
+    let channel_id = webhook.get::<&str, Uuid>("channel").to_string();
+
+    // Get guild
+    let pre_guild = database
+        .query_one(
+            "SELECT * FROM guilds WHERE id = $1",
+            &[&webhook.get::<&str, Uuid>("guild")],
+        )
+        .await;
+
+    if pre_guild.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let guild = pre_guild.unwrap();
+
+    // Check if the channel still exists
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+
+    if !channels.iter().any(|channel| channel.id == channel_id) {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let message = utils::messages::create_message(
+        database,
+        &channel_id,
+        Some(&guild_id),
+        Message {
+            webhook: Some(MessageWebhook {
+                id: webhook_id.to_string(),
+                username: body
+                    .username
+                    .clone()
+                    .unwrap_or(webhook.get::<&str, String>("name")),
+                avatar: body
+                    .avatar
+                    .clone()
+                    .or(webhook.get::<&str, Option<String>>("avatar")),
+            }),
+            // Webhooks aren't members, so they can't mention roles or everyone
+            ..utils::messages::resolve_guild_mentions(
+                &guild,
+                false,
+                utils::messages::new_message(webhook_id, &body.content, "webhook"),
+            )
+        },
+    )
+    .await?;
+
+    utils::messages::send_message(
+        database,
+        sse_clients,
+        dispatcher,
+        unfurler,
+        Destination::Guild(&guild),
+        &channel_id,
+        &message,
+    )
+    .await?;
+
+    Ok(Json(message))
+}
+
+// Return routes
+pub fn get_routes() -> Vec<Route> {
+    routes![get_webhooks, create_webhook, del_webhook, execute_webhook]
+}
@@ -23,6 +23,7 @@ use std::{
     time::{SystemTime, UNIX_EPOCH},
 };
 use totp_rs::{Rfc6238, Secret, TOTP};
+use uuid::Uuid;
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(crate = "rocket::serde")]
@@ -32,6 +33,14 @@ struct Claims {
     exp: u64,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct BotClaims {
+    sub: String,
+    iss: String,
+    jti: String,
+}
+
 pub fn generate_token(id: String) -> Result<String, jsonwebtoken::errors::Error> {
     let claims = Claims {
         sub: id.to_owned(),
@@ -59,16 +68,45 @@ pub fn generate_token(id: String) -> Result<String, jsonwebtoken::errors::Error>
     Ok(token.unwrap())
 }
 
+// Bot tokens never expire, they are only invalidated by being regenerated
+pub fn generate_bot_token(id: String) -> Result<String, jsonwebtoken::errors::Error> {
+    let claims = BotClaims {
+        sub: id.to_owned(),
+        iss: "flyway-chat-bot".to_owned(),
+        jti: Uuid::new_v4().to_string(),
+    };
+    let jwt_key = env::var("JWT_KEY").unwrap();
+    let key = jwt_key.as_bytes();
+
+    let header = Header {
+        alg: Algorithm::HS512,
+        ..Default::default()
+    };
+
+    encode(&header, &claims, &EncodingKey::from_secret(key))
+}
+
 pub fn validate_token(token: &str) -> bool {
     let jwt_key = env::var("JWT_KEY").unwrap();
     let key = jwt_key.as_bytes();
 
-    return decode::<Claims>(
+    if decode::<Claims>(
         &token,
         &DecodingKey::from_secret(key),
         &Validation::new(Algorithm::HS512),
     )
-    .is_ok();
+    .is_ok()
+    {
+        return true;
+    }
+
+    // Check if it is a bot token
+    let mut validation = Validation::new(Algorithm::HS512);
+    validation.validate_exp = false;
+    validation.set_required_spec_claims(&["sub", "iss"]);
+    validation.set_issuer(&["flyway-chat-bot"]);
+
+    decode::<BotClaims>(token, &DecodingKey::from_secret(key), &validation).is_ok()
 }
 
 pub fn verify_otp(secret: &str, code_to_check: &str) -> bool {
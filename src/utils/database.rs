@@ -67,12 +67,32 @@ async fn init(database: &Client) -> Result<(), Error> {
         verified boolean NOT NULL,
         verificator text,
         otp text,
+        presence text,
+        custom_status text,
+        custom_status_expiration bigint,
+        interactions_url text,
+        interactions_secret text,
+        redirect_uris text[],
         PRIMARY KEY (id)
     )",
             &[],
         )
         .await?;
 
+    // Add the columns missing from tables created before them
+    database
+        .query_opt(
+            "ALTER TABLE users
+        ADD COLUMN IF NOT EXISTS presence text,
+        ADD COLUMN IF NOT EXISTS custom_status text,
+        ADD COLUMN IF NOT EXISTS custom_status_expiration bigint,
+        ADD COLUMN IF NOT EXISTS interactions_url text,
+        ADD COLUMN IF NOT EXISTS interactions_secret text,
+        ADD COLUMN IF NOT EXISTS redirect_uris text[]",
+            &[],
+        )
+        .await?;
+
     database
         .query_opt(
             "CREATE TABLE IF NOT EXISTS guilds (
@@ -93,6 +113,355 @@ async fn init(database: &Client) -> Result<(), Error> {
         )
         .await?;
 
+    database
+        .query_opt(
+            "CREATE INDEX IF NOT EXISTS guilds_search ON guilds USING GIN (
+        to_tsvector('simple', name || ' ' || coalesce(description, ''))
+    ) WHERE public",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE TABLE IF NOT EXISTS dms (
+        id uuid NOT NULL,
+        type text NOT NULL,
+        name text,
+        owner text,
+        recipients text[] NOT NULL,
+        creation bigint NOT NULL,
+        PRIMARY KEY (id)
+    )",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE TABLE IF NOT EXISTS messages (
+        id uuid NOT NULL,
+        channel uuid NOT NULL,
+        guild uuid,
+        author text NOT NULL,
+        content text NOT NULL,
+        creation bigint NOT NULL,
+        edited bigint NOT NULL,
+        type text NOT NULL,
+        atachment text,
+        atachment_id text,
+        embeds jsonb[] NOT NULL DEFAULT '{}',
+        webhook jsonb,
+        mentions text[] NOT NULL DEFAULT '{}',
+        mention_roles text[] NOT NULL DEFAULT '{}',
+        mention_everyone boolean NOT NULL DEFAULT false,
+        emojis jsonb[] NOT NULL DEFAULT '{}',
+        reference jsonb,
+        deleted bigint,
+        deleted_by text,
+        PRIMARY KEY (id)
+    )",
+            &[],
+        )
+        .await?;
+
+    // Add the columns missing from tables created before them
+    database
+        .query_opt(
+            "ALTER TABLE messages
+        ADD COLUMN IF NOT EXISTS embeds jsonb[] NOT NULL DEFAULT '{}',
+        ADD COLUMN IF NOT EXISTS webhook jsonb,
+        ADD COLUMN IF NOT EXISTS mentions text[] NOT NULL DEFAULT '{}',
+        ADD COLUMN IF NOT EXISTS mention_roles text[] NOT NULL DEFAULT '{}',
+        ADD COLUMN IF NOT EXISTS mention_everyone boolean NOT NULL DEFAULT false,
+        ADD COLUMN IF NOT EXISTS emojis jsonb[] NOT NULL DEFAULT '{}',
+        ADD COLUMN IF NOT EXISTS reference jsonb,
+        ADD COLUMN IF NOT EXISTS deleted bigint,
+        ADD COLUMN IF NOT EXISTS deleted_by text",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE INDEX IF NOT EXISTS messages_channel ON messages (channel, creation)",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE INDEX IF NOT EXISTS messages_search ON messages USING GIN (
+        to_tsvector('simple', content)
+    ) WHERE guild IS NOT NULL",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE INDEX IF NOT EXISTS messages_deleted ON messages (channel, deleted) WHERE deleted IS NOT NULL",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE TABLE IF NOT EXISTS message_revisions (
+        id uuid NOT NULL,
+        message uuid NOT NULL,
+        content text NOT NULL,
+        creation bigint NOT NULL,
+        PRIMARY KEY (id)
+    )",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE INDEX IF NOT EXISTS message_revisions_message ON message_revisions (message, creation)",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE TABLE IF NOT EXISTS audit_log (
+        id uuid NOT NULL,
+        guild uuid NOT NULL,
+        author text NOT NULL,
+        action text NOT NULL,
+        target text,
+        details jsonb NOT NULL DEFAULT '{}',
+        creation bigint NOT NULL,
+        PRIMARY KEY (id)
+    )",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE INDEX IF NOT EXISTS audit_log_guild ON audit_log (guild, creation)",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE TABLE IF NOT EXISTS emojis (
+        id uuid NOT NULL,
+        guild uuid NOT NULL,
+        name text NOT NULL,
+        image text NOT NULL,
+        author text NOT NULL,
+        creation bigint NOT NULL,
+        PRIMARY KEY (id),
+        UNIQUE (guild, name)
+    )",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE TABLE IF NOT EXISTS reactions (
+        message uuid NOT NULL,
+        channel uuid NOT NULL,
+        guild uuid,
+        owner text NOT NULL,
+        emoji text NOT NULL,
+        creation bigint NOT NULL,
+        PRIMARY KEY (message, emoji, owner)
+    )",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE TABLE IF NOT EXISTS read_states (
+        owner text NOT NULL,
+        channel uuid NOT NULL,
+        guild uuid,
+        last_message uuid,
+        last_creation bigint,
+        mention_count bigint NOT NULL DEFAULT 0,
+        PRIMARY KEY (owner, channel)
+    )",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE TABLE IF NOT EXISTS attachments (
+        id uuid NOT NULL,
+        channel uuid NOT NULL,
+        guild uuid,
+        message uuid NOT NULL,
+        filename text NOT NULL,
+        content_type text NOT NULL,
+        size bigint NOT NULL,
+        creation bigint NOT NULL,
+        PRIMARY KEY (id)
+    )",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE TABLE IF NOT EXISTS webhooks (
+        id uuid NOT NULL,
+        guild uuid NOT NULL,
+        channel uuid NOT NULL,
+        name text NOT NULL,
+        avatar text,
+        secret text NOT NULL,
+        creator text NOT NULL,
+        creation bigint NOT NULL,
+        PRIMARY KEY (id)
+    )",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE TABLE IF NOT EXISTS commands (
+        id uuid NOT NULL,
+        application text NOT NULL,
+        guild uuid,
+        name text NOT NULL,
+        description text NOT NULL,
+        options jsonb[] NOT NULL,
+        creation bigint NOT NULL,
+        PRIMARY KEY (id)
+    )",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE TABLE IF NOT EXISTS interactions (
+        id uuid NOT NULL,
+        application text NOT NULL,
+        guild uuid NOT NULL,
+        channel uuid NOT NULL,
+        invoker text NOT NULL,
+        command uuid NOT NULL,
+        token text NOT NULL,
+        state text NOT NULL,
+        creation bigint NOT NULL,
+        PRIMARY KEY (id)
+    )",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE TABLE IF NOT EXISTS oauth_consents (
+        owner text NOT NULL,
+        client text NOT NULL,
+        scopes text[] NOT NULL,
+        creation bigint NOT NULL,
+        PRIMARY KEY (owner, client)
+    )",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE TABLE IF NOT EXISTS oauth_codes (
+        code text NOT NULL,
+        client text NOT NULL,
+        owner text NOT NULL,
+        redirect_uri text NOT NULL,
+        scopes text[] NOT NULL,
+        challenge text NOT NULL,
+        expiration bigint NOT NULL,
+        PRIMARY KEY (code)
+    )",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE TABLE IF NOT EXISTS oauth_tokens (
+        token text NOT NULL,
+        refresh text NOT NULL,
+        client text NOT NULL,
+        owner text NOT NULL,
+        scopes text[] NOT NULL,
+        expiration bigint NOT NULL,
+        refresh_expiration bigint NOT NULL,
+        PRIMARY KEY (token)
+    )",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE TABLE IF NOT EXISTS subscriptions (
+        id uuid NOT NULL,
+        guild uuid NOT NULL,
+        url text NOT NULL,
+        secret text NOT NULL,
+        events text[] NOT NULL,
+        enabled boolean NOT NULL,
+        failures integer NOT NULL,
+        creation bigint NOT NULL,
+        PRIMARY KEY (id)
+    )",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE TABLE IF NOT EXISTS subscription_deliveries (
+        id uuid NOT NULL,
+        delivery uuid NOT NULL,
+        subscription uuid NOT NULL,
+        event text NOT NULL,
+        attempt integer NOT NULL,
+        status integer,
+        success boolean NOT NULL,
+        error text,
+        creation bigint NOT NULL,
+        PRIMARY KEY (id)
+    )",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE INDEX IF NOT EXISTS subscription_deliveries_subscription ON subscription_deliveries (subscription, creation)",
+            &[],
+        )
+        .await?;
+
+    database
+        .query_opt(
+            "CREATE TABLE IF NOT EXISTS relationships (
+        owner text NOT NULL,
+        target text NOT NULL,
+        type text NOT NULL,
+        creation bigint NOT NULL,
+        PRIMARY KEY (owner, target)
+    )",
+            &[],
+        )
+        .await?;
+
     database
         .query_opt(
             "CREATE TABLE IF NOT EXISTS meta (
@@ -0,0 +1,83 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::routes::structs::{Message, MessageEmoji};
+
+use tokio_postgres::{Client, Error};
+use uuid::Uuid;
+
+// Get the ID of a custom emoji written as <:name:id>, name:id or id
+pub fn parse_custom_emoji(emoji: &str) -> Option<Uuid> {
+    let emoji = emoji
+        .strip_prefix("<:")
+        .and_then(|emoji| emoji.strip_suffix('>'))
+        .unwrap_or(emoji);
+
+    Uuid::parse_str(emoji.rsplit(':').next().unwrap()).ok()
+}
+
+// Extract the custom emojis of a message's content
+pub fn parse_emojis(content: &str) -> Vec<Uuid> {
+    let mut emojis: Vec<Uuid> = vec![];
+
+    for part in content.split("<:").skip(1) {
+        let Some((emoji, _)) = part.split_once('>') else {
+            continue;
+        };
+
+        if let Some(id) = parse_custom_emoji(emoji) {
+            if !emojis.contains(&id) {
+                emojis.push(id);
+            }
+        }
+    }
+
+    emojis
+}
+
+// Keep the custom emojis of the message that belong to the guild
+pub async fn resolve_emojis(
+    database: &Client,
+    guild_id: &str,
+    message: Message,
+) -> Result<Message, Error> {
+    let ids = parse_emojis(&message.content);
+
+    if ids.is_empty() {
+        return Ok(message);
+    }
+
+    let emojis = database
+        .query(
+            "SELECT * FROM emojis WHERE id = any($1) AND guild = $2",
+            &[&ids, &Uuid::parse_str(guild_id).unwrap()],
+        )
+        .await?;
+
+    Ok(Message {
+        emojis: emojis
+            .iter()
+            .map(|emoji| MessageEmoji {
+                id: emoji.get::<&str, Uuid>("id").to_string(),
+                name: emoji.get::<&str, String>("name"),
+                image: emoji.get::<&str, String>("image"),
+            })
+            .collect(),
+        ..message
+    })
+}
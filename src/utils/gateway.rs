@@ -0,0 +1,479 @@
+/*
+Copyright (C) 2024-2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::utils;
+
+use flate2::{write::ZlibEncoder, Compression};
+use rocket::{
+    futures::{SinkExt, StreamExt},
+    serde::{
+        json::{from_value, json, serde_json, Value},
+        Deserialize, Serialize,
+    },
+    tokio::{
+        self,
+        sync::{mpsc, oneshot, Mutex},
+        task::JoinHandle,
+        time::{sleep_until, Duration, Instant},
+    },
+    Route, State,
+};
+use rocket_ws::{
+    frame::{CloseCode, CloseFrame},
+    stream::DuplexStream,
+    Channel, Message, WebSocket,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Write,
+    sync::Arc,
+};
+use uuid::Uuid;
+
+/* opcodes */
+const DISPATCH: u8 = 0; // server -> client
+const HEARTBEAT: u8 = 1; // client -> server
+const IDENTIFY: u8 = 2; // client -> server
+const RESUME: u8 = 3; // client -> server
+const READY: u8 = 4; // server -> client
+const RESUMED: u8 = 5; // server -> client
+const INVALID_SESSION: u8 = 9; // server -> client
+const HELLO: u8 = 10; // server -> client
+const HEARTBEAT_ACK: u8 = 11; // server -> client
+
+/* close codes */
+const UNKNOWN_OPCODE: u16 = 4001;
+const DECODE_ERROR: u16 = 4002;
+const AUTHENTICATION_FAILED: u16 = 4004;
+const ALREADY_AUTHENTICATED: u16 = 4005;
+const TOO_MANY_CONNECTIONS: u16 = 4008;
+
+// Milliseconds between client heartbeats
+const HEARTBEAT_INTERVAL: u64 = 30_000;
+// Seconds a disconnected session can still be resumed
+const RESUME_TIMEOUT: u64 = 60;
+// Amount of dispatched events kept for resuming
+const REPLAY_SIZE: usize = 250;
+
+pub type GatewaySessions = Arc<Mutex<HashMap<String, DetachedSession>>>;
+
+pub struct GatewaySession {
+    user: String,
+    connection: utils::sse::Connection,
+    receiver: mpsc::UnboundedReceiver<Value>,
+    seq: u64,
+    replay: VecDeque<(u64, Value)>,
+}
+
+// Disconnected session, owned by a task recording its events until resumed or timed out
+pub struct DetachedSession {
+    user: String,
+    id: Uuid,
+    deadline: Instant,
+    resume: oneshot::Sender<()>,
+    task: JoinHandle<Option<GatewaySession>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct Frame {
+    op: u8,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    d: Option<Value>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    s: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct IdentifyData {
+    token: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct ResumeData {
+    token: String,
+    session_id: String,
+    seq: u64,
+}
+
+async fn authenticate(database: &tokio_postgres::Client, token: &str) -> Option<String> {
+    if !utils::account::validate_token(token) {
+        return None;
+    }
+
+    database
+        .query_one("SELECT * FROM users WHERE token = $1", &[&token])
+        .await
+        .ok()
+        .map(|user| user.get::<&str, Uuid>("id").to_string())
+}
+
+async fn send(
+    stream: &mut DuplexStream,
+    compress: bool,
+    frame: Frame,
+) -> rocket_ws::result::Result<()> {
+    let text = serde_json::to_string(&frame).unwrap();
+
+    // Compressed frames are sent as zlib binary messages
+    if compress {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+
+        return stream
+            .send(Message::Binary(encoder.finish().unwrap()))
+            .await;
+    }
+
+    stream.send(Message::Text(text)).await
+}
+
+async fn close(stream: &mut DuplexStream, code: u16, reason: &str) {
+    let _ = stream
+        .close(Some(CloseFrame {
+            code: CloseCode::from(code),
+            reason: reason.to_owned().into(),
+        }))
+        .await;
+}
+
+// Keep the event in case the client has to resume
+fn record(session: &mut GatewaySession, event: Value) {
+    session.seq += 1;
+
+    session.replay.push_back((session.seq, event));
+    if session.replay.len() > REPLAY_SIZE {
+        session.replay.pop_front();
+    }
+}
+
+async fn dispatch(
+    stream: &mut DuplexStream,
+    compress: bool,
+    session: &mut GatewaySession,
+    event: Value,
+) -> rocket_ws::result::Result<()> {
+    record(session, event.clone());
+
+    send(
+        stream,
+        compress,
+        Frame {
+            op: DISPATCH,
+            d: Some(event),
+            s: Some(session.seq),
+        },
+    )
+    .await
+}
+
+// Keep the session around for resuming until the deadline
+async fn detach(
+    gateway_sessions: &GatewaySessions,
+    session_id: String,
+    mut session: GatewaySession,
+    deadline: Instant,
+) {
+    session.connection.detach().await;
+
+    let id = Uuid::new_v4();
+    let user = session.user.clone();
+    let (resume, mut resumed) = oneshot::channel();
+
+    // Lock first so the task can't time out before the session is inserted
+    let mut sessions_lock = gateway_sessions.lock().await;
+
+    let gateway_sessions = gateway_sessions.clone();
+    let key = session_id.clone();
+    let task = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                Some(event) = session.receiver.recv() => record(&mut session, event),
+                _ = &mut resumed => break,
+                _ = sleep_until(deadline) => {
+                    let mut sessions_lock = gateway_sessions.lock().await;
+
+                    // Check if the session wasn't taken by a resume meanwhile
+                    if sessions_lock.get(&key).is_some_and(|detached| detached.id == id) {
+                        sessions_lock.remove(&key);
+                        return None;
+                    }
+
+                    break;
+                }
+            }
+        }
+
+        while let Ok(event) = session.receiver.try_recv() {
+            record(&mut session, event);
+        }
+
+        Some(session)
+    });
+
+    sessions_lock.insert(
+        session_id,
+        DetachedSession {
+            user,
+            id,
+            deadline,
+            resume,
+            task,
+        },
+    );
+}
+
+async fn recv(session: &mut Option<(String, GatewaySession)>) -> Option<Value> {
+    match session {
+        Some((_, session)) => session.receiver.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[get("/gateway?<compress>")]
+fn gateway<'r>(
+    ws: WebSocket,
+    compress: Option<&'r str>,
+    sse_clients: &'r State<crate::SSEClients>,
+    presences: &'r State<utils::presence::Presences>,
+    gateway_sessions: &'r State<GatewaySessions>,
+    database: &'r State<crate::Database>,
+) -> Channel<'r> {
+    let compress = compress == Some("zlib");
+
+    ws.channel(move |mut stream| {
+        Box::pin(async move {
+            send(
+                &mut stream,
+                compress,
+                Frame {
+                    op: HELLO,
+                    d: Some(json!({ "heartbeat_interval": HEARTBEAT_INTERVAL })),
+                    s: None,
+                },
+            )
+            .await?;
+
+            let timeout = Duration::from_millis(HEARTBEAT_INTERVAL + HEARTBEAT_INTERVAL / 2);
+            let mut deadline = Instant::now() + timeout;
+            let mut session: Option<(String, GatewaySession)> = None;
+
+            loop {
+                tokio::select! {
+                    message = stream.next() => {
+                        // Parse frame
+                        let frame = match message {
+                            Some(Ok(Message::Text(text))) => serde_json::from_str::<Frame>(&text),
+                            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                            Some(Ok(_)) => continue,
+                        };
+
+                        if frame.is_err() {
+                            close(&mut stream, DECODE_ERROR, "Decode error").await;
+                            break;
+                        }
+
+                        let frame = frame.unwrap();
+
+                        match frame.op {
+                            HEARTBEAT => {
+                                deadline = Instant::now() + timeout;
+
+                                if send(&mut stream, compress, Frame { op: HEARTBEAT_ACK, d: None, s: None }).await.is_err() {
+                                    break;
+                                }
+                            }
+                            IDENTIFY | RESUME if session.is_some() => {
+                                close(&mut stream, ALREADY_AUTHENTICATED, "Already authenticated").await;
+                                break;
+                            }
+                            IDENTIFY => {
+                                let data = from_value::<IdentifyData>(frame.d.unwrap_or(Value::Null));
+
+                                if data.is_err() {
+                                    close(&mut stream, DECODE_ERROR, "Decode error").await;
+                                    break;
+                                }
+
+                                // Authenticate
+                                let user_id = authenticate(database, &data.unwrap().token).await;
+
+                                if user_id.is_none() {
+                                    close(&mut stream, AUTHENTICATION_FAILED, "Authentication failed").await;
+                                    break;
+                                }
+
+                                let user_id = user_id.unwrap();
+
+                                // Register on the event pipeline
+                                let connection = utils::sse::connect(sse_clients, presences, database, user_id.clone()).await;
+
+                                if connection.is_err() {
+                                    close(&mut stream, TOO_MANY_CONNECTIONS, "Too many connections").await;
+                                    break;
+                                }
+
+                                let (connection, receiver) = connection.unwrap();
+                                let session_id = Uuid::new_v4().to_string();
+                                let ready = Frame {
+                                    op: READY,
+                                    d: Some(json!({ "session_id": session_id, "user_id": user_id })),
+                                    s: None,
+                                };
+
+                                session = Some((session_id, GatewaySession {
+                                    user: user_id,
+                                    connection,
+                                    receiver,
+                                    seq: 0,
+                                    replay: VecDeque::new(),
+                                }));
+
+                                if send(&mut stream, compress, ready).await.is_err() {
+                                    break;
+                                }
+                            }
+                            RESUME => {
+                                let data = from_value::<ResumeData>(frame.d.unwrap_or(Value::Null));
+
+                                if data.is_err() {
+                                    close(&mut stream, DECODE_ERROR, "Decode error").await;
+                                    break;
+                                }
+
+                                let data = data.unwrap();
+                                let user_id = authenticate(database, &data.token).await;
+
+                                let mut sessions_lock = gateway_sessions.lock().await;
+
+                                // Check if the session exists and belongs to the user
+                                if user_id.is_none()
+                                    || sessions_lock
+                                        .get(&data.session_id)
+                                        .is_none_or(|detached| Some(&detached.user) != user_id.as_ref())
+                                {
+                                    drop(sessions_lock);
+
+                                    if send(&mut stream, compress, Frame { op: INVALID_SESSION, d: None, s: None }).await.is_err() {
+                                        break;
+                                    }
+
+                                    continue;
+                                }
+
+                                // Take the detached session back from its task
+                                let detached = sessions_lock.remove(&data.session_id).unwrap();
+                                drop(sessions_lock);
+
+                                let _ = detached.resume.send(());
+                                let pre_session = detached.task.await.ok().flatten();
+
+                                if pre_session.is_none() {
+                                    if send(&mut stream, compress, Frame { op: INVALID_SESSION, d: None, s: None }).await.is_err() {
+                                        break;
+                                    }
+
+                                    continue;
+                                }
+
+                                let pre_session = pre_session.unwrap();
+
+                                // Check if the missed events can be replayed, and the user can open another connection
+                                let invalid = data.seq > pre_session.seq
+                                    || pre_session
+                                        .replay
+                                        .front()
+                                        .is_some_and(|(seq, _)| *seq > data.seq + 1);
+
+                                if invalid || pre_session.connection.attach().await.is_err() {
+                                    detach(gateway_sessions, data.session_id, pre_session, detached.deadline).await;
+
+                                    if invalid {
+                                        if send(&mut stream, compress, Frame { op: INVALID_SESSION, d: None, s: None }).await.is_err() {
+                                            break;
+                                        }
+
+                                        continue;
+                                    }
+
+                                    close(&mut stream, TOO_MANY_CONNECTIONS, "Too many connections").await;
+                                    break;
+                                }
+
+                                // Replay missed events
+                                let missed: Vec<(u64, Value)> = pre_session
+                                    .replay
+                                    .iter()
+                                    .filter(|(seq, _)| *seq > data.seq)
+                                    .cloned()
+                                    .collect();
+
+                                session = Some((data.session_id, pre_session));
+
+                                let mut failed = false;
+                                for (seq, event) in missed {
+                                    if send(&mut stream, compress, Frame { op: DISPATCH, d: Some(event), s: Some(seq) }).await.is_err() {
+                                        failed = true;
+                                        break;
+                                    }
+                                }
+
+                                if failed || send(&mut stream, compress, Frame { op: RESUMED, d: None, s: None }).await.is_err() {
+                                    break;
+                                }
+                            }
+                            _ => {
+                                close(&mut stream, UNKNOWN_OPCODE, "Unknown opcode").await;
+                                break;
+                            }
+                        }
+                    }
+                    event = recv(&mut session) => {
+                        if event.is_none() {
+                            break;
+                        }
+
+                        if dispatch(&mut stream, compress, &mut session.as_mut().unwrap().1, event.unwrap()).await.is_err() {
+                            break;
+                        }
+                    }
+                    _ = sleep_until(deadline) => {
+                        close(&mut stream, CloseCode::Away.into(), "Heartbeat timed out").await;
+                        break;
+                    }
+                }
+            }
+
+            // Keep the session around for resuming
+            if let Some((session_id, session)) = session {
+                detach(gateway_sessions, session_id, session, Instant::now() + Duration::from_secs(RESUME_TIMEOUT)).await;
+            }
+
+            Ok(())
+        })
+    })
+}
+
+// Return route
+pub fn get_route() -> Vec<Route> {
+    routes![gateway]
+}
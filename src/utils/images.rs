@@ -0,0 +1,92 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{utils::storage::StorageBackend, AppError};
+
+use image::{imageops::FilterType, ImageFormat, ImageReader, Limits};
+use rocket::{http::Status, tokio::task};
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+
+// Maximum size of an uploaded image, in bytes
+pub const MAX_IMAGE_SIZE: u64 = 8 * 1024 * 1024;
+// Sizes every image is resized to, the first one is used for hashing
+pub const IMAGE_SIZES: [u32; 4] = [512, 256, 128, 64];
+// Maximum width and height of an uploaded image
+const MAX_IMAGE_DIMENSION: u32 = 4096;
+
+// Decode, crop and re-encode the image to every size, dropping its metadata
+fn process_image(data: &[u8]) -> Result<Vec<(u32, Vec<u8>)>, Status> {
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|_| Status::BadRequest)?;
+
+    // Check if the format is allowed
+    if !matches!(
+        reader.format(),
+        Some(ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP)
+    ) {
+        return Err(Status::UnsupportedMediaType);
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    reader.limits(limits);
+
+    let image = reader.decode().map_err(|_| Status::BadRequest)?;
+
+    let mut sizes = Vec::new();
+    for size in IMAGE_SIZES {
+        let mut encoded = Cursor::new(Vec::new());
+        image
+            .resize_to_fill(size, size, FilterType::Lanczos3)
+            .write_to(&mut encoded, ImageFormat::Png)
+            .map_err(|_| Status::InternalServerError)?;
+
+        sizes.push((size, encoded.into_inner()));
+    }
+
+    Ok(sizes)
+}
+
+// Process and store the image under its content hash, returning the hash
+pub async fn store_image(
+    storage: &StorageBackend,
+    prefix: &str,
+    data: Vec<u8>,
+) -> Result<String, AppError> {
+    let sizes = task::spawn_blocking(move || process_image(&data))
+        .await
+        .map_err(|_| AppError(Status::InternalServerError))?
+        .map_err(AppError)?;
+
+    let hash = hex::encode(Sha256::digest(&sizes[0].1));
+
+    for (size, encoded) in sizes {
+        storage
+            .put(
+                &format!("{}/{}/{}.png", prefix, hash, size),
+                &encoded,
+                "image/png",
+            )
+            .await?;
+    }
+
+    Ok(hash)
+}
@@ -0,0 +1,587 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    routes::structs::{Member, Message, MessageReference, Role},
+    utils::{
+        self,
+        permissions::{check_channel_permission, ChannelPermissions},
+        storage::StorageBackend,
+        structs::SSEEvent,
+        subscriptions::Dispatcher,
+        unfurl::Unfurler,
+    },
+    AppError,
+};
+
+use rocket::{
+    http::Status,
+    serde::json::{from_value, to_value, Value},
+    tokio::{
+        self,
+        time::{sleep, Duration},
+    },
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio_postgres::{Client, Error, Row};
+use uuid::Uuid;
+
+// Maximum length of a message's content
+pub const MAX_CONTENT_LENGTH: usize = 4000;
+// Amount of messages returned by default, and at most
+pub const DEFAULT_LIMIT: i64 = 50;
+pub const MAX_LIMIT: i64 = 100;
+// Mention of every member that can view the channel
+pub const EVERYONE_MENTION: &str = "@everyone";
+// Maximum length of a replied message's preview, in characters
+pub const PREVIEW_LENGTH: usize = 100;
+// Seconds a deleted message is kept for moderators before being purged
+pub const DELETED_RETENTION: i64 = 30 * 24 * 60 * 60;
+// Seconds between purges of deleted messages
+const PURGE_INTERVAL: u64 = 60 * 60;
+
+pub fn parse_message(row: &Row) -> Message {
+    Message {
+        id: row.get::<&str, Uuid>("id").to_string(),
+        author: row.get::<&str, String>("author"),
+        content: row.get::<&str, String>("content"),
+        creation: row.get::<&str, i64>("creation"),
+        edited: row.get::<&str, i64>("edited"),
+        r#type: row.get::<&str, String>("type"),
+        atachment: row
+            .try_get::<&str, Option<String>>("atachment")
+            .unwrap_or(None),
+        atachment_id: row
+            .try_get::<&str, Option<String>>("atachment_id")
+            .unwrap_or(None),
+        embeds: from_value(Value::Array(row.get::<&str, Vec<Value>>("embeds"))).unwrap(),
+        webhook: row
+            .get::<&str, Option<Value>>("webhook")
+            .map(|webhook| from_value(webhook).unwrap()),
+        mentions: row.get::<&str, Vec<String>>("mentions"),
+        mention_roles: row.get::<&str, Vec<String>>("mention_roles"),
+        mention_everyone: row.get::<&str, bool>("mention_everyone"),
+        reactions: vec![],
+        emojis: from_value(Value::Array(row.get::<&str, Vec<Value>>("emojis"))).unwrap(),
+        reference: row
+            .get::<&str, Option<Value>>("reference")
+            .map(|reference| from_value(reference).unwrap()),
+    }
+}
+
+pub fn new_message(author: &str, content: &str, r#type: &str) -> Message {
+    Message {
+        id: Uuid::new_v4().to_string(),
+        author: author.to_string(),
+        content: content.to_string(),
+        creation: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64,
+        edited: 0,
+        r#type: r#type.to_string(),
+        atachment: None,
+        atachment_id: None,
+        embeds: vec![],
+        webhook: None,
+        mentions: vec![],
+        mention_roles: vec![],
+        mention_everyone: false,
+        reactions: vec![],
+        emojis: vec![],
+        reference: None,
+    }
+}
+
+// Extract the mentioned users and roles, and whether everyone is mentioned
+pub fn parse_mentions(content: &str) -> (Vec<String>, Vec<String>, bool) {
+    let mut users: Vec<String> = vec![];
+    let mut roles: Vec<String> = vec![];
+
+    for part in content.split("<@").skip(1) {
+        let Some((id, _)) = part.split_once('>') else {
+            continue;
+        };
+
+        let (mentioned, id) = match id.strip_prefix('&') {
+            Some(id) => (&mut roles, id),
+            None => (&mut users, id),
+        };
+
+        if Uuid::parse_str(id).is_ok() && !mentioned.iter().any(|mention| mention == id) {
+            mentioned.push(id.to_string());
+        }
+    }
+
+    (users, roles, content.contains(EVERYONE_MENTION))
+}
+
+// Keep the mentions of the guild's members and roles, roles and everyone need the permission
+pub fn resolve_guild_mentions(
+    guild: &Row,
+    can_mention_everyone: bool,
+    message: Message,
+) -> Message {
+    let (users, roles, everyone) = parse_mentions(&message.content);
+
+    let members: Vec<Member> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("members"))).unwrap();
+    let guild_roles: Vec<Role> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("roles"))).unwrap();
+
+    Message {
+        mentions: users
+            .into_iter()
+            .filter(|user| members.iter().any(|member| member.id == *user))
+            .collect(),
+        mention_roles: roles
+            .into_iter()
+            .filter(|role| can_mention_everyone && guild_roles.iter().any(|r| r.id == *role))
+            .collect(),
+        mention_everyone: can_mention_everyone && everyone,
+        ..message
+    }
+}
+
+// Keep the mentions of the DM's recipients
+pub fn resolve_dm_mentions(recipients: &[String], message: Message) -> Message {
+    let (users, _, _) = parse_mentions(&message.content);
+
+    Message {
+        mentions: users
+            .into_iter()
+            .filter(|user| recipients.contains(user))
+            .collect(),
+        ..message
+    }
+}
+
+// Count the mention and broadcast mentionCreated event to every recipient the message mentions
+pub async fn broadcast_mentions(
+    database: &Client,
+    sse_clients: &crate::SSEClients,
+    guild: Option<&Row>,
+    channel_id: &str,
+    message: &Message,
+    recipients: &[String],
+) -> Result<(), Error> {
+    let guild_id = guild.map(|guild| guild.get::<&str, Uuid>("id").to_string());
+    let members: Vec<Member> = guild
+        .map(|guild| from_value(Value::Array(guild.get::<&str, Vec<Value>>("members"))).unwrap())
+        .unwrap_or_default();
+
+    // Check if mentioned directly, through a role or by everyone
+    let mentioned: Vec<String> = recipients
+        .iter()
+        .filter(|recipient| **recipient != message.author)
+        .filter(|recipient| {
+            message.mention_everyone
+                || message.mentions.contains(recipient)
+                || members.iter().any(|member| {
+                    member.id == **recipient
+                        && member
+                            .roles
+                            .iter()
+                            .any(|role| message.mention_roles.contains(role))
+                })
+        })
+        .cloned()
+        .collect();
+
+    if mentioned.is_empty() {
+        return Ok(());
+    }
+
+    utils::read_states::add_mentions(database, &mentioned, channel_id, guild_id.as_deref()).await?;
+
+    for recipient in mentioned.iter() {
+        utils::sse::broadcast(
+            sse_clients,
+            recipient,
+            SSEEvent::MentionCreated {
+                guild_id: guild_id.as_deref(),
+                channel_id,
+                message,
+            },
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+// Channel a message is sent to
+pub enum Destination<'a> {
+    Guild(&'a Row),
+    Dm(&'a [String]),
+}
+
+// Get every member that can view the channel
+pub fn get_recipients(guild: &Row, channel_id: &str) -> Vec<String> {
+    let members: Vec<Member> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("members"))).unwrap();
+
+    members
+        .into_iter()
+        .filter(|member| {
+            check_channel_permission(
+                guild,
+                &channel_id.to_string(),
+                &member.id,
+                ChannelPermissions::VIEW_CHANNEL,
+            )
+        })
+        .map(|member| member.id)
+        .collect()
+}
+
+// Deliver a created message to its recipients, the guild's subscriptions, the mentioned users and the unfurler
+pub async fn send_message(
+    database: &Client,
+    sse_clients: &crate::SSEClients,
+    dispatcher: &Dispatcher,
+    unfurler: &Unfurler,
+    destination: Destination<'_>,
+    channel_id: &str,
+    message: &Message,
+) -> Result<(), Error> {
+    let (guild, guild_id, recipients) = match destination {
+        Destination::Guild(guild) => (
+            Some(guild),
+            Some(guild.get::<&str, Uuid>("id").to_string()),
+            get_recipients(guild, channel_id),
+        ),
+        Destination::Dm(recipients) => (None, None, recipients.to_vec()),
+    };
+
+    // Broadcast messageCreated event to every recipient
+    for recipient in recipients.iter() {
+        utils::sse::broadcast(
+            sse_clients,
+            recipient,
+            SSEEvent::MessageCreated {
+                guild_id: guild_id.as_deref(),
+                channel_id,
+                message,
+            },
+        )
+        .await;
+    }
+
+    // Dispatch messageCreated event to the guild's subscriptions
+    if let Some(guild_id) = &guild_id {
+        utils::subscriptions::dispatch(
+            dispatcher,
+            guild_id,
+            SSEEvent::MessageCreated {
+                guild_id: Some(guild_id),
+                channel_id,
+                message,
+            },
+        );
+    }
+
+    broadcast_mentions(
+        database,
+        sse_clients,
+        guild,
+        channel_id,
+        message,
+        &recipients,
+    )
+    .await?;
+
+    utils::unfurl::queue(
+        unfurler,
+        guild_id.as_deref(),
+        channel_id,
+        message,
+        recipients,
+    );
+
+    Ok(())
+}
+
+// Reference the replied message, mentioning its author if asked to
+pub async fn reply(
+    database: &Client,
+    channel_id: &str,
+    reply_to: &str,
+    mention_author: bool,
+    message: Message,
+) -> Result<Message, AppError> {
+    // Check if the replied message ID is valid
+    let reply_to = Uuid::parse_str(reply_to).map_err(|_| AppError(Status::BadRequest))?;
+
+    // Check if the replied message exists in the channel
+    let pre_replied = database
+        .query_one(
+            "SELECT * FROM messages WHERE id = $1 AND channel = $2 AND deleted IS NULL",
+            &[&reply_to, &Uuid::parse_str(channel_id).unwrap()],
+        )
+        .await;
+
+    if pre_replied.is_err() {
+        return Err(AppError(Status::NotFound));
+    }
+
+    let replied = parse_message(&pre_replied.unwrap());
+
+    let mut mentions = message.mentions;
+    if mention_author && replied.author != message.author && !mentions.contains(&replied.author) {
+        mentions.push(replied.author.clone());
+    }
+
+    Ok(Message {
+        reference: Some(MessageReference {
+            message_id: replied.id,
+            author: replied.author,
+            preview: replied.content.chars().take(PREVIEW_LENGTH).collect(),
+        }),
+        mentions,
+        ..message
+    })
+}
+
+pub async fn create_message(
+    database: &Client,
+    channel_id: &str,
+    guild_id: Option<&str>,
+    message: Message,
+) -> Result<Message, Error> {
+    // Resolve the guild's custom emojis used in the message
+    let message = match guild_id {
+        Some(guild_id) => utils::emojis::resolve_emojis(database, guild_id, message).await?,
+        None => message,
+    };
+
+    database.execute("INSERT INTO messages (id, channel, guild, author, content, creation, edited, type, atachment, atachment_id, webhook, mentions, mention_roles, mention_everyone, emojis, reference) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)",
+    &[
+        &Uuid::parse_str(&message.id).unwrap(),
+        &Uuid::parse_str(channel_id).unwrap(),
+        &guild_id.map(|guild_id| Uuid::parse_str(guild_id).unwrap()),
+        &message.author,
+        &message.content,
+        &message.creation,
+        &message.edited,
+        &message.r#type,
+        &message.atachment,
+        &message.atachment_id,
+        &message.webhook.as_ref().map(|webhook| to_value(webhook).unwrap()),
+        &message.mentions,
+        &message.mention_roles,
+        &message.mention_everyone,
+        &message
+            .emojis
+            .iter()
+            .map(|emoji| to_value(emoji).unwrap())
+            .collect::<Vec<Value>>(),
+        &message
+            .reference
+            .as_ref()
+            .map(|reference| to_value(reference).unwrap()),
+    ]).await?;
+
+    Ok(message)
+}
+
+// Get the channel's messages, newest first
+pub async fn get_messages(
+    database: &Client,
+    user_id: &str,
+    channel_id: &str,
+    before: Option<&str>,
+    limit: Option<i64>,
+) -> Result<Vec<Message>, Error> {
+    let messages = database
+        .query(
+            "SELECT * FROM messages WHERE channel = $1 AND deleted IS NULL AND (
+                $2::uuid IS NULL OR (creation, id) < (
+                    SELECT creation, id FROM messages WHERE id = $2
+                )
+            ) ORDER BY creation DESC, id DESC LIMIT $3",
+            &[
+                &Uuid::parse_str(channel_id).unwrap(),
+                &before.and_then(|before| Uuid::parse_str(before).ok()),
+                &limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+            ],
+        )
+        .await?;
+
+    utils::reactions::attach_reactions(
+        database,
+        user_id,
+        messages.iter().map(parse_message).collect(),
+    )
+    .await
+}
+
+// Copy the message with new content, leaving what is resolved from the content to resolve again
+pub fn edited_message(row: &Row, content: &str) -> Message {
+    Message {
+        content: content.to_string(),
+        edited: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64,
+        embeds: vec![],
+        mentions: vec![],
+        mention_roles: vec![],
+        mention_everyone: false,
+        emojis: vec![],
+        ..parse_message(row)
+    }
+}
+
+// Keep the previous revision, then replace the content and what is resolved from it
+pub async fn edit_message(
+    database: &Client,
+    guild_id: Option<&str>,
+    previous: &Message,
+    mut message: Message,
+) -> Result<Message, Error> {
+    // Keep mentioning the replied message's author
+    if let Some(reference) = &previous.reference {
+        if previous.mentions.contains(&reference.author)
+            && !message.mentions.contains(&reference.author)
+        {
+            message.mentions.push(reference.author.clone());
+        }
+    }
+
+    let message = match guild_id {
+        Some(guild_id) => utils::emojis::resolve_emojis(database, guild_id, message).await?,
+        None => message,
+    };
+
+    // Keep the replaced content as a revision and update the message in a single statement
+    database
+        .execute(
+            "WITH revised AS (
+                INSERT INTO message_revisions (id, message, content, creation)
+                SELECT $1, id, content, CASE WHEN edited = 0 THEN creation ELSE edited END
+                FROM messages WHERE id = $8
+            )
+            UPDATE messages SET content = $2, edited = $3, mentions = $4, mention_roles = $5,
+            mention_everyone = $6, emojis = $7, embeds = '{}' WHERE id = $8",
+            &[
+                &Uuid::new_v4(),
+                &message.content,
+                &message.edited,
+                &message.mentions,
+                &message.mention_roles,
+                &message.mention_everyone,
+                &message
+                    .emojis
+                    .iter()
+                    .map(|emoji| to_value(emoji).unwrap())
+                    .collect::<Vec<Value>>(),
+                &Uuid::parse_str(&message.id).unwrap(),
+            ],
+        )
+        .await?;
+
+    Ok(message)
+}
+
+// Hide the message, keeping it for moderators until the retention period is over
+pub async fn delete_message(
+    database: &Client,
+    message_id: &str,
+    user_id: &str,
+) -> Result<(), Error> {
+    database
+        .execute(
+            "UPDATE messages SET deleted = $1, deleted_by = $2 WHERE id = $3",
+            &[
+                &(SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs() as i64),
+                &user_id,
+                &Uuid::parse_str(message_id).unwrap(),
+            ],
+        )
+        .await?;
+
+    Ok(())
+}
+
+// Permanently delete the channel's messages, or the deleted ones past their retention,
+// with their revisions, reactions and attachments
+pub async fn purge_messages(
+    database: &Client,
+    storage: &StorageBackend,
+    channel_id: Option<&str>,
+    expiry: Option<i64>,
+) -> Result<(), Error> {
+    let channel = channel_id.map(|channel_id| Uuid::parse_str(channel_id).unwrap());
+
+    let attachments = database
+        .query(
+            "WITH purged AS (
+                DELETE FROM messages
+                WHERE ($1::uuid IS NOT NULL AND channel = $1) OR ($2::bigint IS NOT NULL AND deleted < $2)
+                RETURNING id
+            ), revisions AS (
+                DELETE FROM message_revisions WHERE message IN (SELECT id FROM purged)
+            ), reactions AS (
+                DELETE FROM reactions WHERE message IN (SELECT id FROM purged)
+            ), read_states AS (
+                DELETE FROM read_states WHERE channel = $1
+            )
+            DELETE FROM attachments WHERE message IN (SELECT id FROM purged) OR channel = $1
+            RETURNING id, channel, filename",
+            &[&channel, &expiry],
+        )
+        .await?;
+
+    for attachment in attachments {
+        let key = format!(
+            "attachments/{}/{}/{}",
+            attachment.get::<&str, Uuid>("channel"),
+            attachment.get::<&str, Uuid>("id"),
+            attachment.get::<&str, String>("filename")
+        );
+
+        if let Err(e) = storage.delete(&key).await {
+            println!("{:}", e);
+        }
+    }
+
+    Ok(())
+}
+
+// Periodically purge the deleted messages past their retention period
+pub fn spawn(database: crate::Database, storage: StorageBackend) {
+    tokio::spawn(async move {
+        loop {
+            let expiry = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64
+                - DELETED_RETENTION;
+
+            if let Err(e) = purge_messages(&database, &storage, None, Some(expiry)).await {
+                println!("{:}", e);
+            }
+
+            sleep(Duration::from_secs(PURGE_INTERVAL)).await;
+        }
+    });
+}
@@ -20,5 +20,20 @@ pub mod structs;
 
 pub mod account;
 pub mod database;
+pub mod emojis;
+pub mod gateway;
+pub mod images;
+pub mod messages;
+pub mod network;
+pub mod oauth;
 pub mod permissions;
+pub mod pins;
+pub mod presence;
+pub mod reactions;
+pub mod read_states;
 pub mod sse;
+pub mod storage;
+pub mod subscriptions;
+#[cfg(test)]
+pub mod test_server;
+pub mod unfurl;
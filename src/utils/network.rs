@@ -0,0 +1,212 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect::Policy,
+    Client, Url,
+};
+use rocket::tokio::{net::lookup_host, time::Duration};
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+// Ports outgoing requests can be made to
+const ALLOWED_PORTS: [u16; 2] = [80, 443];
+
+// Resolves hostnames to public addresses only
+pub struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+
+            if addrs.is_empty() {
+                return Err("host does not resolve to a public address".into());
+            }
+
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+// Check if the address is reachable on the public internet
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || a == 0
+                || a >= 240
+                || (a == 100 && (64..128).contains(&b))
+                || (a == 192 && b == 0 && (c == 0 || c == 2))
+                || (a == 198 && (18..20).contains(&b))
+                || (a == 198 && b == 51 && c == 100)
+                || (a == 203 && b == 0 && c == 113))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(ip));
+            }
+
+            let segments = ip.segments();
+            let octets = ip.octets();
+
+            // Check the IPv4 address embedded by NAT64 (64:ff9b::/96) and IPv4-compatible (::/96) addresses
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+                || (segments[..6] == [0; 6] && !ip.is_loopback() && !ip.is_unspecified())
+            {
+                return is_public(IpAddr::from([
+                    octets[12], octets[13], octets[14], octets[15],
+                ]));
+            }
+
+            // Check the IPv4 address embedded by 6to4 (2002::/16) addresses
+            if segments[0] == 0x2002 {
+                return is_public(IpAddr::from([octets[2], octets[3], octets[4], octets[5]]));
+            }
+
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (segments[0] & 0xfe00) == 0xfc00
+                || (segments[0] & 0xffc0) == 0xfe80
+                || segments[..2] == [0x2001, 0xdb8])
+        }
+    }
+}
+
+// Check if the URL can be requested: HTTP on a standard port, and no private IP literal
+pub fn is_allowed(url: &Url) -> bool {
+    if !["http", "https"].contains(&url.scheme()) {
+        return false;
+    }
+
+    if !url
+        .port_or_known_default()
+        .is_some_and(|port| ALLOWED_PORTS.contains(&port))
+    {
+        return false;
+    }
+
+    // IP literals skip the resolver
+    match url.host_str() {
+        Some(host) => host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .map_or(true, is_public),
+        None => false,
+    }
+}
+
+// Check if the URL is valid and can be requested
+pub fn is_allowed_url(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| is_allowed(&url))
+}
+
+// Build an HTTP client that only connects to public addresses
+pub fn public_client(timeout: u64, redirect: Policy, user_agent: &str) -> Client {
+    Client::builder()
+        .timeout(Duration::from_secs(timeout))
+        .connect_timeout(Duration::from_secs(timeout))
+        .redirect(redirect)
+        .dns_resolver(Arc::new(PublicResolver))
+        .no_proxy()
+        .user_agent(user_agent)
+        .build()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_non_public_addresses() {
+        for ip in [
+            "93.184.216.34",
+            "1.1.1.1",
+            "2606:4700:4700::1111",
+            "64:ff9b::5db8:d822",
+            "2002:5db8:d822::1",
+            "::ffff:93.184.216.34",
+        ] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+
+        for ip in [
+            "0.0.0.0",
+            "10.0.0.1",
+            "100.64.0.1",
+            "127.0.0.1",
+            "169.254.169.254",
+            "172.16.0.1",
+            "192.0.0.8",
+            "192.0.2.1",
+            "192.168.1.1",
+            "198.18.0.1",
+            "198.51.100.1",
+            "203.0.113.1",
+            "224.0.0.1",
+            "240.0.0.1",
+            "255.255.255.255",
+            "::",
+            "::1",
+            "::127.0.0.1",
+            "::ffff:10.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+            "64:ff9b::7f00:1",
+            "2002:7f00:1::1",
+            "2002:a00:1::1",
+            "2001:db8::1",
+            "fc00::1",
+            "fd12:3456::1",
+            "fe80::1",
+            "ff02::1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn allows_public_http_urls_only() {
+        assert!(is_allowed_url("https://example.com/hook"));
+        assert!(is_allowed_url("http://93.184.216.34/hook"));
+
+        assert!(!is_allowed_url("ftp://example.com/hook"));
+        assert!(!is_allowed_url("https://example.com:8443/hook"));
+        assert!(!is_allowed_url("http://127.0.0.1/hook"));
+        assert!(!is_allowed_url("http://169.254.169.254/latest/meta-data"));
+        assert!(!is_allowed_url("http://10.0.0.1/hook"));
+        assert!(!is_allowed_url("http://[::1]/hook"));
+        assert!(!is_allowed_url("not a url"));
+    }
+}
@@ -0,0 +1,144 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use rand::Rng;
+use reqwest::Url;
+use rocket::{http::Method, Route};
+use sha2::{Digest, Sha256};
+use std::{
+    net::IpAddr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio_postgres::Client;
+
+// Scopes a third-party application can request
+pub const SCOPES: [&str; 4] = ["identify", "guilds", "guilds.join", "messages.read"];
+
+// Routes an OAuth2 access token can reach, and the scope each one requires
+const SCOPED_ROUTES: [(Method, &str, &str); 8] = [
+    (Method::Get, "/users/@me", "identify"),
+    (Method::Get, "/users/@me/guilds", "guilds"),
+    (Method::Get, "/invites/<invite_code>", "guilds.join"),
+    (Method::Put, "/invites/<invite_code>", "guilds.join"),
+    (
+        Method::Get,
+        "/guilds/<guild_id>/channels/<channel_id>/messages",
+        "messages.read",
+    ),
+    (
+        Method::Get,
+        "/channels/<channel_id>/messages",
+        "messages.read",
+    ),
+    (Method::Get, "/users/@me/channels", "messages.read"),
+    (
+        Method::Get,
+        "/attachments/<attachment_id>/<filename>",
+        "messages.read",
+    ),
+];
+
+// Check if users can be sent back to the URI: HTTPS, HTTP on loopback, or an app's reverse domain scheme
+pub fn is_valid_redirect_uri(redirect_uri: &str) -> bool {
+    let Ok(url) = Url::parse(redirect_uri) else {
+        return false;
+    };
+
+    if url.fragment().is_some() {
+        return false;
+    }
+
+    match url.scheme() {
+        "https" => url.host().is_some(),
+        "http" => url.host_str().is_some_and(|host| {
+            host == "localhost"
+                || host
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .parse::<IpAddr>()
+                    .is_ok_and(|ip| ip.is_loopback())
+        }),
+        // Private-use schemes of native apps, like com.example.app
+        scheme => scheme.contains('.'),
+    }
+}
+
+pub fn generate_token() -> String {
+    hex::encode(rand::rng().random::<[u8; 32]>())
+}
+
+// Tokens and codes are only stored hashed
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// Get the user of an access token, if its scopes allow the route
+pub async fn authenticate(database: &Client, token: &str, route: Option<&Route>) -> Option<String> {
+    let route = route?;
+    let scope = SCOPED_ROUTES
+        .iter()
+        .find(|(method, path, _)| *method == route.method && *path == route.uri.path())
+        .map(|(_, _, scope)| scope.to_string())?;
+
+    // Check if the token is valid, and its client still exists
+    let token = database
+        .query_one(
+            "SELECT * FROM oauth_tokens WHERE token = $1 AND expiration > $2
+            AND EXISTS (SELECT 1 FROM users WHERE users.id::text = oauth_tokens.client AND users.type = 'BOT')",
+            &[
+                &hash_token(token),
+                &(SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs() as i64),
+            ],
+        )
+        .await
+        .ok()?;
+
+    if !token.get::<&str, Vec<String>>("scopes").contains(&scope) {
+        return None;
+    }
+
+    Some(token.get::<&str, String>("owner"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_redirect_uris() {
+        assert!(is_valid_redirect_uri("https://app.example.com/callback"));
+        assert!(is_valid_redirect_uri("http://localhost:3000/callback"));
+        assert!(is_valid_redirect_uri("http://127.0.0.1:8080/callback"));
+        assert!(is_valid_redirect_uri("http://[::1]/callback"));
+        assert!(is_valid_redirect_uri("com.example.app:/callback"));
+
+        assert!(!is_valid_redirect_uri("http://app.example.com/callback"));
+        assert!(!is_valid_redirect_uri(
+            "https://app.example.com/callback#token"
+        ));
+        assert!(!is_valid_redirect_uri("javascript:alert(1)"));
+        assert!(!is_valid_redirect_uri(
+            "data:text/html,<script>alert(1)</script>"
+        ));
+        assert!(!is_valid_redirect_uri("file:///etc/passwd"));
+        assert!(!is_valid_redirect_uri("/relative/callback"));
+    }
+}
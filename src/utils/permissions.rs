@@ -33,6 +33,7 @@ bitflags! {
         const KICK_MEMBERS = 1 << 5;
         const BAN_MEMBERS = 1 << 6;
         const MANAGE_GUILD = 1 << 7;
+        const MANAGE_EMOJIS = 1 << 8;
 
         const ADMINISTRATOR = !0;
     }
@@ -45,6 +46,7 @@ bitflags! {
         const SEND_MESSAGES = 1 << 1;
         const MANAGE_CHANNEL = 1 << 2;
         const MANAGE_MESSAGES = 1 << 3;
+        const MENTION_EVERYONE = 1 << 4;
 
         const ADMINISTRATOR = !0;
     }
@@ -88,11 +90,19 @@ pub fn check_channel_permission(
         from_value(Value::Array(guild.get::<&str, Vec<Value>>("members"))).unwrap();
     let channels: Vec<Channel> =
         from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
-    let channel = channels
+    let mut channel = channels
         .iter()
         .find(|channel| channel.id == *channel_id)
         .unwrap();
 
+    // Threads inherit the permissions of their parent channel
+    if let Some(parent) = &channel.parent {
+        channel = channels
+            .iter()
+            .find(|channel| channel.id == *parent)
+            .unwrap();
+    }
+
     // Get the member's roles
     let member_roles_ids = members
         .iter()
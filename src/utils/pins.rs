@@ -0,0 +1,116 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    routes::structs::{Channel, Member},
+    utils::{
+        self,
+        permissions::{check_channel_permission, ChannelPermissions},
+    },
+};
+
+use rocket::serde::json::{from_value, Value};
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+// Broadcast channelPinsUpdated event to every member that can view the channel
+pub async fn broadcast(
+    sse_clients: &crate::SSEClients,
+    guild: &Row,
+    guild_id: &str,
+    channel_id: &str,
+    pins: &Vec<String>,
+) {
+    let members: Vec<Member> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("members"))).unwrap();
+
+    for member in members.iter().filter(|member| {
+        check_channel_permission(
+            guild,
+            &channel_id.to_string(),
+            &member.id,
+            ChannelPermissions::VIEW_CHANNEL,
+        )
+    }) {
+        utils::sse::broadcast(
+            sse_clients,
+            &member.id,
+            utils::structs::SSEEvent::ChannelPinsUpdated {
+                guild_id,
+                channel_id,
+                pins,
+            },
+        )
+        .await;
+    }
+}
+
+// Remove the deleted messages from the channel's pins
+pub async fn remove(
+    database: &tokio_postgres::Client,
+    sse_clients: &crate::SSEClients,
+    guild: &Row,
+    guild_id: &str,
+    channel_id: &str,
+    message_ids: &[String],
+) -> Result<(), tokio_postgres::Error> {
+    let channels: Vec<Channel> =
+        from_value(Value::Array(guild.get::<&str, Vec<Value>>("channels"))).unwrap();
+    let pre_channel = channels
+        .into_iter()
+        .find(|channel| channel.id == channel_id);
+
+    // Check if any of the messages is pinned
+    if pre_channel
+        .as_ref()
+        .is_none_or(|channel| !channel.pins.iter().any(|pin| message_ids.contains(pin)))
+    {
+        return Ok(());
+    }
+
+    let mut channel = pre_channel.unwrap();
+
+    database
+        .execute(
+            "UPDATE guilds SET channels = array_replace(channels,
+                                    (
+                                        SELECT channel
+                                        FROM unnest(channels) AS channel
+                                        WHERE channel->>'id' = $1
+                                    ),
+                                    (
+                                        SELECT jsonb_set(channel, '{pins}', (channel->'pins') - $2::text[])
+                                        FROM unnest(channels) AS channel
+                                        WHERE channel->>'id' = $1
+                                    )
+                                )
+            WHERE id = $3",
+            &[
+                &channel_id,
+                &message_ids,
+                &Uuid::parse_str(guild_id).unwrap(),
+            ],
+        )
+        .await?;
+
+    channel.pins.retain(|pin| !message_ids.contains(pin));
+
+    broadcast(sse_clients, guild, guild_id, channel_id, &channel.pins).await;
+
+    Ok(())
+}
@@ -0,0 +1,223 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::routes::structs::ReturnedPresence;
+use crate::utils;
+
+use rocket::tokio::sync::Mutex;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+// Presence of every connected user
+pub type Presences = Arc<Mutex<HashMap<String, Presence>>>;
+
+#[derive(Clone, Debug)]
+pub struct Presence {
+    pub status: String,
+    pub custom_status: Option<String>,
+    pub custom_status_expiration: Option<i64>,
+}
+
+// Presence as seen by others (or by the user themselves)
+pub fn returned_presence(user_id: &str, presence: Option<&Presence>, me: bool) -> ReturnedPresence {
+    if presence.is_none() || (!me && presence.unwrap().status == "invisible") {
+        return ReturnedPresence {
+            user_id: user_id.to_string(),
+            status: "offline".to_string(),
+            custom_status: None,
+            custom_status_expiration: None,
+        };
+    }
+
+    let presence = presence.unwrap();
+
+    // Check if the custom status has expired
+    let expired = presence.custom_status_expiration.is_some_and(|expiration| {
+        expiration
+            <= SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64
+    });
+
+    ReturnedPresence {
+        user_id: user_id.to_string(),
+        status: presence.status.clone(),
+        custom_status: if expired {
+            None
+        } else {
+            presence.custom_status.clone()
+        },
+        custom_status_expiration: if expired {
+            None
+        } else {
+            presence.custom_status_expiration
+        },
+    }
+}
+
+// Get every other user sharing a guild with the user
+pub async fn get_recipients(
+    database: &tokio_postgres::Client,
+    user_id: &str,
+) -> Result<Vec<String>, tokio_postgres::Error> {
+    let rows = database
+        .query(
+            "SELECT DISTINCT member->>'id' AS id
+            FROM guilds, unnest(members) AS member
+            WHERE member->>'id' != $1 AND EXISTS (
+                SELECT 1
+                FROM unnest(members) AS me
+                WHERE me->>'id' = $1
+            )",
+            &[&user_id],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| row.get::<&str, String>("id"))
+        .collect())
+}
+
+// Broadcast presenceUpdated event to the user and everyone currently sharing a guild
+pub async fn broadcast(
+    sse_clients: &crate::SSEClients,
+    database: &tokio_postgres::Client,
+    user_id: &str,
+    presence: Option<&Presence>,
+) {
+    let recipients = get_recipients(database, user_id).await.unwrap_or_default();
+    let public_presence = returned_presence(user_id, presence, false);
+
+    for recipient in recipients {
+        utils::sse::broadcast(
+            sse_clients,
+            &recipient,
+            utils::structs::SSEEvent::PresenceUpdated {
+                presence: &public_presence,
+            },
+        )
+        .await;
+    }
+
+    utils::sse::broadcast(
+        sse_clients,
+        user_id,
+        utils::structs::SSEEvent::PresenceUpdated {
+            presence: &returned_presence(user_id, presence, true),
+        },
+    )
+    .await;
+}
+
+// The user opened their first connection
+pub async fn connected(
+    sse_clients: &crate::SSEClients,
+    presences: &Presences,
+    database: &tokio_postgres::Client,
+    user_id: &str,
+) {
+    let user = database
+        .query_one(
+            "SELECT * FROM users WHERE id = $1",
+            &[&Uuid::parse_str(user_id).unwrap()],
+        )
+        .await;
+
+    if user.is_err() {
+        return;
+    }
+
+    let user = user.unwrap();
+    let presence = Presence {
+        status: user
+            .try_get::<&str, Option<String>>("presence")
+            .unwrap_or(None)
+            .unwrap_or("online".to_string()),
+        custom_status: user
+            .try_get::<&str, Option<String>>("custom_status")
+            .unwrap_or(None),
+        custom_status_expiration: user
+            .try_get::<&str, Option<i64>>("custom_status_expiration")
+            .unwrap_or(None),
+    };
+
+    presences
+        .lock()
+        .await
+        .insert(user_id.to_string(), presence.clone());
+
+    broadcast(sse_clients, database, user_id, Some(&presence)).await;
+}
+
+// Get the presence of every visible user sharing a guild with the user
+pub async fn snapshot(
+    presences: &Presences,
+    database: &tokio_postgres::Client,
+    user_id: &str,
+) -> Vec<ReturnedPresence> {
+    let recipients = get_recipients(database, user_id).await.unwrap_or_default();
+    let presences_lock = presences.lock().await;
+
+    recipients
+        .iter()
+        .filter_map(|recipient| {
+            presences_lock
+                .get(recipient)
+                .filter(|presence| presence.status != "invisible")
+                .map(|presence| returned_presence(recipient, Some(presence), false))
+        })
+        .collect()
+}
+
+// The user closed their last connection
+pub async fn disconnected(
+    sse_clients: &crate::SSEClients,
+    presences: &Presences,
+    database: &tokio_postgres::Client,
+    user_id: &str,
+) {
+    let presence = presences.lock().await.remove(user_id);
+
+    // Invisible users already appear offline
+    if presence
+        .as_ref()
+        .is_none_or(|presence| presence.status == "invisible")
+    {
+        return;
+    }
+
+    let recipients = get_recipients(database, user_id).await.unwrap_or_default();
+    let public_presence = returned_presence(user_id, None, false);
+
+    for recipient in recipients {
+        utils::sse::broadcast(
+            sse_clients,
+            &recipient,
+            utils::structs::SSEEvent::PresenceUpdated {
+                presence: &public_presence,
+            },
+        )
+        .await;
+    }
+}
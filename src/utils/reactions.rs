@@ -0,0 +1,100 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    routes::structs::{Message, MessageReaction},
+    utils::emojis::parse_custom_emoji,
+};
+
+use tokio_postgres::{Client, Error};
+use uuid::Uuid;
+
+// Maximum length of an emoji
+pub const MAX_EMOJI_LENGTH: usize = 32;
+// Maximum amount of different emojis reacted on a message
+pub const MAX_EMOJIS_PER_MESSAGE: i64 = 20;
+
+// Check if the emoji is a unicode emoji
+pub fn is_valid_emoji(emoji: &str) -> bool {
+    !emoji.is_empty()
+        && emoji.len() <= MAX_EMOJI_LENGTH
+        && !emoji.is_ascii()
+        && !emoji
+            .chars()
+            .any(|c| c.is_ascii_alphabetic() || c.is_whitespace() || c.is_control())
+}
+
+// Get the stored form of an emoji, custom emojis being stored by ID
+pub fn stored_emoji(emoji: &str) -> String {
+    parse_custom_emoji(emoji)
+        .map(|id| id.to_string())
+        .unwrap_or(emoji.to_string())
+}
+
+// Get the displayed form of a stored emoji, custom emojis being displayed as name:id
+pub async fn displayed_emoji(database: &Client, emoji: &str) -> Result<String, Error> {
+    let custom = database
+        .query_opt(
+            "SELECT name || ':' || id AS emoji FROM emojis WHERE id::text = $1",
+            &[&emoji],
+        )
+        .await?;
+
+    Ok(custom
+        .map(|custom| custom.get::<&str, String>("emoji"))
+        .unwrap_or(emoji.to_string()))
+}
+
+// Fill in the aggregated reactions of the messages, as seen by the user
+pub async fn attach_reactions(
+    database: &Client,
+    user_id: &str,
+    messages: Vec<Message>,
+) -> Result<Vec<Message>, Error> {
+    let ids: Vec<Uuid> = messages
+        .iter()
+        .map(|message| Uuid::parse_str(&message.id).unwrap())
+        .collect();
+
+    let reactions = database
+        .query(
+            "SELECT reactions.message, coalesce(emojis.name || ':' || emojis.id, reactions.emoji) AS emoji,
+                count(*) AS count, bool_or(reactions.owner = $2) AS me
+            FROM reactions LEFT JOIN emojis ON emojis.id::text = reactions.emoji
+            WHERE reactions.message = any($1)
+            GROUP BY reactions.message, 2 ORDER BY min(reactions.creation)",
+            &[&ids, &user_id],
+        )
+        .await?;
+
+    Ok(messages
+        .into_iter()
+        .map(|message| Message {
+            reactions: reactions
+                .iter()
+                .filter(|reaction| reaction.get::<&str, Uuid>("message").to_string() == message.id)
+                .map(|reaction| MessageReaction {
+                    emoji: reaction.get::<&str, String>("emoji"),
+                    count: reaction.get::<&str, i64>("count"),
+                    me: reaction.get::<&str, bool>("me"),
+                })
+                .collect(),
+            ..message
+        })
+        .collect())
+}
@@ -0,0 +1,126 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::routes::structs::{Message, ReadState};
+
+use tokio_postgres::{Client, Error};
+use uuid::Uuid;
+
+// Unread messages are counted up to this amount
+pub const MAX_UNREAD_COUNT: i64 = 100;
+
+// Get the user's read states of the channels
+pub async fn get_read_states(
+    database: &Client,
+    user_id: &str,
+    channels: &Vec<Uuid>,
+) -> Result<Vec<ReadState>, Error> {
+    let read_states = database
+        .query(
+            "SELECT channel.id AS channel, read_state.last_message,
+                coalesce(read_state.mention_count, 0) AS mention_count,
+                (
+                    SELECT count(*) FROM (
+                        SELECT 1 FROM messages
+                        WHERE messages.channel = channel.id AND messages.author <> $1 AND messages.deleted IS NULL AND (
+                            read_state.last_message IS NULL
+                            OR (messages.creation, messages.id) > (read_state.last_creation, read_state.last_message)
+                        ) LIMIT $3
+                    ) AS unread
+                ) AS unread_count
+            FROM unnest($2::uuid[]) AS channel(id)
+            LEFT JOIN read_states AS read_state
+            ON read_state.owner = $1 AND read_state.channel = channel.id",
+            &[&user_id, channels, &MAX_UNREAD_COUNT],
+        )
+        .await?;
+
+    Ok(read_states
+        .iter()
+        .map(|read_state| ReadState {
+            channel_id: read_state.get::<&str, Uuid>("channel").to_string(),
+            last_message_id: read_state
+                .get::<&str, Option<Uuid>>("last_message")
+                .map(|id| id.to_string()),
+            unread_count: read_state.get::<&str, i64>("unread_count"),
+            mention_count: read_state.get::<&str, i64>("mention_count"),
+        })
+        .collect())
+}
+
+// Mark the channel as read up to the message, recounting the mentions after it
+pub async fn ack(
+    database: &Client,
+    user_id: &str,
+    channel_id: &str,
+    guild_id: Option<&str>,
+    roles: &Vec<String>,
+    message: &Message,
+) -> Result<ReadState, Error> {
+    let channel = Uuid::parse_str(channel_id).unwrap();
+
+    database
+        .execute(
+            "INSERT INTO read_states (owner, channel, guild, last_message, last_creation, mention_count)
+            VALUES ($1, $2, $3, $4, $5, (
+                SELECT count(*) FROM messages
+                WHERE channel = $2 AND author <> $1 AND deleted IS NULL AND (creation, id) > ($5, $4)
+                AND ($1 = any(mentions) OR mention_everyone OR mention_roles && $6)
+            ))
+            ON CONFLICT (owner, channel) DO UPDATE SET
+                last_message = EXCLUDED.last_message,
+                last_creation = EXCLUDED.last_creation,
+                mention_count = EXCLUDED.mention_count",
+            &[
+                &user_id,
+                &channel,
+                &guild_id.map(|guild_id| Uuid::parse_str(guild_id).unwrap()),
+                &Uuid::parse_str(&message.id).unwrap(),
+                &message.creation,
+                roles,
+            ],
+        )
+        .await?;
+
+    Ok(get_read_states(database, user_id, &vec![channel])
+        .await?
+        .remove(0))
+}
+
+// Count a new mention for every mentioned user
+pub async fn add_mentions(
+    database: &Client,
+    users: &Vec<String>,
+    channel_id: &str,
+    guild_id: Option<&str>,
+) -> Result<(), Error> {
+    database
+        .execute(
+            "INSERT INTO read_states (owner, channel, guild, mention_count)
+            SELECT owner, $2, $3, 1 FROM unnest($1::text[]) AS owner
+            ON CONFLICT (owner, channel) DO UPDATE SET mention_count = read_states.mention_count + 1",
+            &[
+                users,
+                &Uuid::parse_str(channel_id).unwrap(),
+                &guild_id.map(|guild_id| Uuid::parse_str(guild_id).unwrap()),
+            ],
+        )
+        .await?;
+
+    Ok(())
+}
@@ -16,18 +16,158 @@ You should have received a copy of the GNU Affero General Public License
 along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::utils::{
+    self,
+    presence::Presences,
+    structs::{SSEEvent, VersionedEvent, EVENT_VERSION},
+};
+
 use rocket::{
     http::Status,
     response::stream::{Event, EventStream},
-    tokio::sync::mpsc,
+    serde::json::{serde_json, Json, Value},
+    tokio::{self, sync::mpsc, time::Duration},
     Route, State,
 };
+use schemars::{schema_for, Schema};
+use uuid::Uuid;
+
+// Seconds between heartbeat comments sent on idle streams
+const HEARTBEAT_INTERVAL: u64 = 15;
+// Maximum amount of simultaneous connections (SSE or gateway) per user
+const MAX_STREAMS_PER_USER: usize = 5;
+
+pub struct SSEClient {
+    pub id: Uuid,
+    pub user: String,
+    pub sender: mpsc::UnboundedSender<Value>,
+    // Detached gateway sessions keep receiving events without counting as a connection
+    pub detached: bool,
+}
+
+// Removes the client from the registry once dropped
+pub struct Connection {
+    pub id: Uuid,
+    user: String,
+    sse_clients: crate::SSEClients,
+    presences: Presences,
+    database: crate::Database,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let id = self.id;
+        let user = self.user.clone();
+        let sse_clients = self.sse_clients.clone();
+        let presences = self.presences.clone();
+        let database = self.database.clone();
+
+        tokio::spawn(async move {
+            let mut client_lock = sse_clients.lock().await;
+            client_lock.retain(|client| client.id != id);
+
+            // Check if it was the user's last connection
+            let last = !client_lock.iter().any(|client| client.user == user);
+            drop(client_lock);
+
+            if last {
+                utils::presence::disconnected(&sse_clients, &presences, &database, &user).await;
+            }
+        });
+    }
+}
+
+impl Connection {
+    pub async fn detach(&self) {
+        let mut client_lock = self.sse_clients.lock().await;
+
+        if let Some(client) = client_lock.iter_mut().find(|client| client.id == self.id) {
+            client.detached = true;
+        }
+    }
+
+    pub async fn attach(&self) -> Result<(), Status> {
+        let mut client_lock = self.sse_clients.lock().await;
+
+        // Check if the user has too many connections open
+        if client_lock
+            .iter()
+            .filter(|client| client.user == self.user && !client.detached)
+            .count()
+            >= MAX_STREAMS_PER_USER
+        {
+            return Err(Status::TooManyRequests);
+        }
+
+        if let Some(client) = client_lock.iter_mut().find(|client| client.id == self.id) {
+            client.detached = false;
+        }
+
+        Ok(())
+    }
+}
+
+// Register a new event receiver for the user, shared by every transport
+pub async fn connect(
+    sse_clients: &crate::SSEClients,
+    presences: &Presences,
+    database: &crate::Database,
+    user_id: String,
+) -> Result<(Connection, mpsc::UnboundedReceiver<Value>), Status> {
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    let mut client_lock = sse_clients.lock().await;
+
+    // Check if the user has too many connections open
+    let count = client_lock
+        .iter()
+        .filter(|client| client.user == user_id && !client.detached)
+        .count();
+
+    if count >= MAX_STREAMS_PER_USER {
+        return Err(Status::TooManyRequests);
+    }
+
+    let online = client_lock.iter().any(|client| client.user == user_id);
+
+    let connection = Connection {
+        id: Uuid::new_v4(),
+        user: user_id.clone(),
+        sse_clients: sse_clients.clone(),
+        presences: presences.clone(),
+        database: database.clone(),
+    };
+
+    client_lock.push(SSEClient {
+        id: connection.id,
+        user: user_id.clone(),
+        sender: tx.clone(),
+        detached: false,
+    });
+
+    drop(client_lock);
+
+    // The user just came online
+    if !online {
+        utils::presence::connected(sse_clients, presences, database, &user_id).await;
+    }
+
+    // Send the presence of everyone sharing a guild to the new connection
+    for presence in utils::presence::snapshot(presences, database, &user_id).await {
+        let _ = tx.send(versioned(SSEEvent::PresenceUpdated {
+            presence: &presence,
+        }));
+    }
+
+    Ok((connection, rx))
+}
 
 #[get("/sse?<token>")]
 async fn stream(
     token: &str,
     sse_clients: &State<crate::SSEClients>,
-    database: &State<tokio_postgres::Client>,
+    presences: &State<Presences>,
+    database: &State<crate::Database>,
 ) -> Result<EventStream![], Status> {
     let user = database
         .query_one("SELECT * FROM users WHERE token = $1", &[&token])
@@ -37,35 +177,52 @@ async fn stream(
         return Err(Status::Unauthorized);
     }
 
-    let (tx, mut rx) = mpsc::unbounded_channel();
-
-    let mut client_lock = sse_clients.lock().await;
-    client_lock.push((user.unwrap().get::<&str, uuid::Uuid>("id").to_string(), tx));
+    let (connection, mut rx) = connect(
+        sse_clients,
+        presences,
+        database,
+        user.unwrap().get::<&str, Uuid>("id").to_string(),
+    )
+    .await?;
 
     Ok(EventStream! {
+        let _connection = connection;
+
         while let Some(event) = rx.recv().await {
-            yield event;
+            yield Event::json(&event);
         }
+    }
+    .heartbeat(Duration::from_secs(HEARTBEAT_INTERVAL)))
+}
+
+fn versioned(message: SSEEvent<'_>) -> Value {
+    serde_json::to_value(VersionedEvent {
+        v: EVENT_VERSION,
+        event: &message,
     })
+    .unwrap()
 }
 
-pub async fn broadcast(
-    sse_clients: &State<crate::SSEClients>,
-    id: &str,
-    message: crate::utils::structs::SSEEvent<'_>,
-) {
+pub async fn broadcast(sse_clients: &crate::SSEClients, id: &str, message: SSEEvent<'_>) {
+    let event = versioned(message);
     let mut client_lock = sse_clients.lock().await;
 
     client_lock.retain(|client| {
-        if id == client.0 {
-            client.1.send(Event::json(&message)).is_ok()
+        if id == client.user {
+            client.sender.send(event.clone()).is_ok()
         } else {
             true
         }
     });
 }
 
+// JSON Schema of every event payload, for client codegen
+#[get("/events/schema", format = "json")]
+async fn schema() -> Json<Schema> {
+    Json(schema_for!(VersionedEvent<'static>))
+}
+
 // Return route
 pub fn get_route() -> Vec<Route> {
-    routes![stream]
+    routes![stream, schema]
 }
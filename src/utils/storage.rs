@@ -0,0 +1,501 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Method, StatusCode, Url};
+use rocket::{time::OffsetDateTime, tokio::fs};
+use sha2::{Digest, Sha256};
+use std::{
+    env,
+    io::{Error, ErrorKind},
+    path::PathBuf,
+    sync::Arc,
+};
+
+pub type StorageBackend = Arc<dyn Storage>;
+
+#[rocket::async_trait]
+pub trait Storage: Send + Sync {
+    async fn put(&self, key: &str, data: &[u8], content_type: &str) -> Result<(), Error>;
+    async fn get(&self, key: &str) -> Result<Vec<u8>, Error>;
+    async fn delete(&self, key: &str) -> Result<(), Error>;
+}
+
+// Create the storage backend selected by STORAGE (local or s3)
+pub fn from_env() -> StorageBackend {
+    match env::var("STORAGE").unwrap_or("local".to_string()).as_str() {
+        "s3" => Arc::new(S3Storage {
+            endpoint: Url::parse(&env::var("S3_ENDPOINT").unwrap()).unwrap(),
+            bucket: env::var("S3_BUCKET").unwrap(),
+            region: env::var("S3_REGION").unwrap_or("us-east-1".to_string()),
+            access_key: env::var("S3_ACCESS_KEY").unwrap(),
+            secret_key: env::var("S3_SECRET_KEY").unwrap(),
+            client: Client::new(),
+        }),
+        _ => Arc::new(LocalStorage {
+            root: PathBuf::from(env::var("STORAGE_PATH").unwrap_or("./storage".to_string())),
+        }),
+    }
+}
+
+/* local filesystem */
+pub struct LocalStorage {
+    pub root: PathBuf,
+}
+
+impl LocalStorage {
+    fn path(&self, key: &str) -> Result<PathBuf, Error> {
+        // Keys are relative, reject anything escaping the root
+        if key
+            .split('/')
+            .any(|part| part.is_empty() || part == "." || part == "..")
+        {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid storage key"));
+        }
+
+        Ok(self.root.join(key))
+    }
+}
+
+#[rocket::async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, data: &[u8], _content_type: &str) -> Result<(), Error> {
+        let path = self.path(key)?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        fs::write(path, data).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, Error> {
+        fs::read(self.path(key)?).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        fs::remove_file(self.path(key)?).await
+    }
+}
+
+/* S3-compatible object storage, using path-style requests */
+pub struct S3Storage {
+    pub endpoint: Url,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    pub client: Client,
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn uri_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+// Credentials and scope of AWS Signature Version 4
+struct Credentials<'a> {
+    access_key: &'a str,
+    secret_key: &'a str,
+    region: &'a str,
+    service: &'a str,
+}
+
+// Build the canonical request, the headers being lowercase and sorted by name
+fn canonical_request(
+    method: &Method,
+    path: &str,
+    query: &str,
+    headers: &[(&str, &str)],
+    payload_hash: &str,
+) -> String {
+    let mut parameters: Vec<&str> = query.split('&').filter(|part| !part.is_empty()).collect();
+    parameters.sort();
+
+    format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method,
+        path,
+        parameters.join("&"),
+        headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect::<String>(),
+        headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<&str>>()
+            .join(";"),
+        payload_hash
+    )
+}
+
+// Derive the key of the day, region and service from the secret key
+fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    hmac_sha256(
+        &hmac_sha256(
+            &hmac_sha256(
+                &hmac_sha256(format!("AWS4{}", secret_key).as_bytes(), date),
+                region,
+            ),
+            service,
+        ),
+        "aws4_request",
+    )
+}
+
+// Get the authorization header of a request, dated by its x-amz-date header
+fn authorization(
+    credentials: &Credentials,
+    method: &Method,
+    path: &str,
+    query: &str,
+    headers: &[(&str, &str)],
+    payload_hash: &str,
+) -> String {
+    let timestamp = headers
+        .iter()
+        .find(|(name, _)| *name == "x-amz-date")
+        .map(|(_, value)| *value)
+        .unwrap();
+    let date = &timestamp[..8];
+
+    let scope = format!(
+        "{}/{}/{}/aws4_request",
+        date, credentials.region, credentials.service
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        timestamp,
+        scope,
+        hex::encode(Sha256::digest(
+            canonical_request(method, path, query, headers, payload_hash).as_bytes()
+        ))
+    );
+    let signature = hex::encode(hmac_sha256(
+        &signing_key(
+            credentials.secret_key,
+            date,
+            credentials.region,
+            credentials.service,
+        ),
+        &string_to_sign,
+    ));
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key,
+        scope,
+        headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<&str>>()
+            .join(";"),
+        signature
+    )
+}
+
+impl S3Storage {
+    // Send a signed request
+    async fn request(
+        &self,
+        method: Method,
+        key: &str,
+        body: Vec<u8>,
+        content_type: Option<&str>,
+    ) -> Result<reqwest::Response, Error> {
+        let now = OffsetDateTime::now_utc();
+        let date = format!("{:04}{:02}{:02}", now.year(), now.month() as u8, now.day());
+        let timestamp = format!(
+            "{}T{:02}{:02}{:02}Z",
+            date,
+            now.hour(),
+            now.minute(),
+            now.second()
+        );
+
+        let path = format!(
+            "{}/{}/{}",
+            self.endpoint.path().trim_end_matches('/'),
+            uri_encode(&self.bucket),
+            key.split('/')
+                .map(uri_encode)
+                .collect::<Vec<String>>()
+                .join("/")
+        );
+        let host = match self.endpoint.port() {
+            Some(port) => format!("{}:{}", self.endpoint.host_str().unwrap(), port),
+            None => self.endpoint.host_str().unwrap().to_string(),
+        };
+        let payload_hash = hex::encode(Sha256::digest(&body));
+        let authorization = authorization(
+            &Credentials {
+                access_key: &self.access_key,
+                secret_key: &self.secret_key,
+                region: &self.region,
+                service: "s3",
+            },
+            &method,
+            &path,
+            "",
+            &[
+                ("host", &host),
+                ("x-amz-content-sha256", &payload_hash),
+                ("x-amz-date", &timestamp),
+            ],
+            &payload_hash,
+        );
+
+        let mut url = self.endpoint.clone();
+        url.set_path(&path);
+
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", timestamp)
+            .header("authorization", authorization)
+            .body(body);
+
+        if let Some(content_type) = content_type {
+            request = request.header("content-type", content_type);
+        }
+
+        let response = request.send().await.map_err(Error::other)?;
+
+        match response.status() {
+            status if status.is_success() => Ok(response),
+            StatusCode::NOT_FOUND => Err(Error::new(ErrorKind::NotFound, "object not found")),
+            status => Err(Error::other(format!("storage responded with {}", status))),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Storage for S3Storage {
+    async fn put(&self, key: &str, data: &[u8], content_type: &str) -> Result<(), Error> {
+        self.request(Method::PUT, key, data.to_vec(), Some(content_type))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, Error> {
+        Ok(self
+            .request(Method::GET, key, vec![], None)
+            .await?
+            .bytes()
+            .await
+            .map_err(Error::other)?
+            .to_vec())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.request(Method::DELETE, key, vec![], None).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::utils::test_server;
+
+    fn storage(endpoint: &str) -> S3Storage {
+        S3Storage {
+            endpoint: Url::parse(endpoint).unwrap(),
+            bucket: "flyway".to_string(),
+            region: "eu-west-1".to_string(),
+            access_key: "AKIDEXAMPLE".to_string(),
+            secret_key: "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY".to_string(),
+            client: Client::new(),
+        }
+    }
+
+    #[test]
+    fn derives_signing_key() {
+        // Example from the AWS Signature Version 4 documentation
+        assert_eq!(
+            hex::encode(signing_key(
+                "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+                "20120215",
+                "us-east-1",
+                "iam"
+            )),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    // Requests of the AWS Signature Version 4 test suite
+    fn sign_test_suite(method: Method, query: &str) -> String {
+        authorization(
+            &Credentials {
+                access_key: "AKIDEXAMPLE",
+                secret_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+                region: "us-east-1",
+                service: "service",
+            },
+            &method,
+            "/",
+            query,
+            &[
+                ("host", "example.amazonaws.com"),
+                ("x-amz-date", "20150830T123600Z"),
+            ],
+            EMPTY_PAYLOAD_HASH,
+        )
+    }
+
+    const EMPTY_PAYLOAD_HASH: &str =
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn builds_canonical_request() {
+        // get-vanilla-query-order-key-case from the test suite
+        assert_eq!(
+            canonical_request(
+                &Method::GET,
+                "/",
+                "Param2=value2&Param1=value1",
+                &[
+                    ("host", "example.amazonaws.com"),
+                    ("x-amz-date", "20150830T123600Z"),
+                ],
+                EMPTY_PAYLOAD_HASH,
+            ),
+            "GET\n/\nParam1=value1&Param2=value2\n\
+             host:example.amazonaws.com\n\
+             x-amz-date:20150830T123600Z\n\n\
+             host;x-amz-date\n\
+             e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn signs_test_suite_requests() {
+        // get-vanilla
+        assert_eq!(
+            sign_test_suite(Method::GET, ""),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+
+        // get-vanilla-query-order-key-case
+        assert_eq!(
+            sign_test_suite(Method::GET, "Param2=value2&Param1=value1"),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500"
+        );
+
+        // post-vanilla
+        assert_eq!(
+            sign_test_suite(Method::POST, ""),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b"
+        );
+    }
+
+    #[rocket::async_test]
+    async fn sends_signed_requests() {
+        let (endpoint, requests) = test_server::start(vec![(200, "hello")]).await;
+        let storage = storage(&endpoint);
+
+        storage
+            .put("attachments/42/red dot.png", b"hello", "image/png")
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.get("attachments/42/red dot.png").await.unwrap(),
+            b"hello"
+        );
+        storage.delete("attachments/42/red dot.png").await.unwrap();
+
+        let requests = requests.lock().await;
+        let lines: Vec<&str> = requests
+            .iter()
+            .map(|request| request.line.as_str())
+            .collect();
+
+        assert_eq!(
+            lines,
+            [
+                "PUT /flyway/attachments/42/red%20dot.png HTTP/1.1",
+                "GET /flyway/attachments/42/red%20dot.png HTTP/1.1",
+                "DELETE /flyway/attachments/42/red%20dot.png HTTP/1.1",
+            ]
+        );
+        assert_eq!(requests[0].body, b"hello");
+        assert_eq!(requests[0].headers["content-type"], "image/png");
+
+        // Check the signed headers the stand-in received
+        for (request, payload_hash) in requests.iter().zip([
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+            EMPTY_PAYLOAD_HASH,
+            EMPTY_PAYLOAD_HASH,
+        ]) {
+            let timestamp = &request.headers["x-amz-date"];
+            let authorization = &request.headers["authorization"];
+            let prefix = format!(
+                "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/{}/eu-west-1/s3/aws4_request, \
+                 SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature=",
+                &timestamp[..8]
+            );
+
+            assert_eq!(
+                request.headers["host"],
+                endpoint.trim_start_matches("http://")
+            );
+            assert_eq!(request.headers["x-amz-content-sha256"], payload_hash);
+            assert_eq!(timestamp.len(), 16);
+            assert!(authorization.starts_with(&prefix));
+            assert_eq!(authorization[prefix.len()..].len(), 64);
+        }
+    }
+
+    #[rocket::async_test]
+    async fn maps_missing_objects() {
+        let (endpoint, _) = test_server::start(vec![(404, "")]).await;
+
+        assert_eq!(
+            storage(&endpoint)
+                .get("attachments/42/missing.png")
+                .await
+                .unwrap_err()
+                .kind(),
+            ErrorKind::NotFound
+        );
+    }
+}
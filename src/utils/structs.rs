@@ -16,9 +16,13 @@ You should have received a copy of the GNU Affero General Public License
 along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use crate::routes::structs::{ReturnedGuild, ReturnedUser, ReturnedUserMe};
+use crate::routes::structs::{
+    Embed, Interaction, Message, ReadState, ReturnedDM, ReturnedEmoji, ReturnedGuild,
+    ReturnedPresence, ReturnedRelationship, ReturnedThread, ReturnedUser, ReturnedUserMe,
+};
 
 use rocket::serde::Serialize;
+use schemars::JsonSchema;
 
 #[macro_export]
 macro_rules! to_json_array {
@@ -29,48 +33,150 @@ macro_rules! to_json_array {
     };
 }
 
-#[derive(Serialize, Debug)]
-#[serde(crate = "rocket::serde")]
-pub struct SSEEvent<'r> {
-    pub event: &'r str,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub user: Option<&'r ReturnedUserMe>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub guild: Option<&'r ReturnedGuild>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub guild_id: Option<&'r str>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub role: Option<&'r str>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub member: Option<&'r ReturnedUser>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub channel: Option<&'r str>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub message: Option<&'r str>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub invite: Option<&'r str>,
+// Bump whenever an event's payload changes in a breaking way
+pub const EVENT_VERSION: u8 = 1;
+
+#[derive(Serialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde", tag = "event", rename_all = "camelCase")]
+pub enum SSEEvent<'r> {
+    GuildJoined {
+        guild: &'r ReturnedGuild,
+    },
+    GuildEdited {
+        guild: &'r ReturnedGuild,
+    },
+    GuildLeft {
+        guild_id: &'r str,
+    },
+    MemberUnbanned {
+        guild_id: &'r str,
+        member: &'r ReturnedUser,
+    },
+    UserEdited {
+        user: &'r ReturnedUserMe,
+    },
+    TypingStarted {
+        guild_id: &'r str,
+        channel_id: &'r str,
+        user_id: &'r str,
+        timestamp: i64,
+    },
+    TypingStopped {
+        guild_id: &'r str,
+        channel_id: &'r str,
+        user_id: &'r str,
+    },
+    PresenceUpdated {
+        presence: &'r ReturnedPresence,
+    },
+    MessageCreated {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        guild_id: Option<&'r str>,
+        channel_id: &'r str,
+        message: &'r Message,
+    },
+    GuildEmojisUpdated {
+        guild_id: &'r str,
+        emojis: &'r Vec<ReturnedEmoji>,
+    },
+    ThreadCreated {
+        guild_id: &'r str,
+        thread: &'r ReturnedThread,
+    },
+    ThreadUpdated {
+        guild_id: &'r str,
+        thread: &'r ReturnedThread,
+    },
+    ChannelPinsUpdated {
+        guild_id: &'r str,
+        channel_id: &'r str,
+        pins: &'r Vec<String>,
+    },
+    ChannelAcked {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        guild_id: Option<&'r str>,
+        read_state: &'r ReadState,
+    },
+    ReactionAdded {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        guild_id: Option<&'r str>,
+        channel_id: &'r str,
+        message_id: &'r str,
+        emoji: &'r str,
+        user_id: &'r str,
+    },
+    ReactionRemoved {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        guild_id: Option<&'r str>,
+        channel_id: &'r str,
+        message_id: &'r str,
+        emoji: &'r str,
+        user_id: &'r str,
+    },
+    MentionCreated {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        guild_id: Option<&'r str>,
+        channel_id: &'r str,
+        message: &'r Message,
+    },
+    MessageUpdated {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        guild_id: Option<&'r str>,
+        channel_id: &'r str,
+        message: &'r Message,
+    },
+    MessageDeleted {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        guild_id: Option<&'r str>,
+        channel_id: &'r str,
+        message_id: &'r str,
+    },
+    MessagesBulkDeleted {
+        guild_id: &'r str,
+        channel_id: &'r str,
+        message_ids: &'r Vec<String>,
+    },
+    MessageRestored {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        guild_id: Option<&'r str>,
+        channel_id: &'r str,
+        message: &'r Message,
+    },
+    MessageEmbedsUpdated {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        guild_id: Option<&'r str>,
+        channel_id: &'r str,
+        message_id: &'r str,
+        embeds: &'r Vec<Embed>,
+    },
+    InteractionCreated {
+        interaction: &'r Interaction,
+    },
+    InteractionDeferred {
+        guild_id: &'r str,
+        channel_id: &'r str,
+        interaction_id: &'r str,
+    },
+    DmCreated {
+        channel: &'r ReturnedDM,
+    },
+    DmEdited {
+        channel: &'r ReturnedDM,
+    },
+    DmLeft {
+        channel_id: &'r str,
+    },
+    RelationshipUpdated {
+        relationship: &'r ReturnedRelationship,
+    },
 }
 
-impl Default for SSEEvent<'_> {
-    fn default() -> SSEEvent<'static> {
-        SSEEvent {
-            event: "unknown",
-            user: None,
-            guild: None,
-            guild_id: None,
-            role: None,
-            member: None,
-            channel: None,
-            message: None,
-            invite: None,
-        }
-    }
+/* Payload sent on every transport */
+#[derive(Serialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct VersionedEvent<'r> {
+    pub v: u8,
+
+    #[serde(flatten)]
+    pub event: &'r SSEEvent<'r>,
 }
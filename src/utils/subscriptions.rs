@@ -0,0 +1,426 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::utils::{
+    self,
+    structs::{SSEEvent, VersionedEvent, EVENT_VERSION},
+};
+
+use hmac::{Hmac, Mac};
+use reqwest::{redirect::Policy, Client};
+use rocket::{
+    serde::json::serde_json,
+    tokio::{
+        self,
+        sync::mpsc,
+        time::{sleep, Duration},
+    },
+};
+use sha2::Sha256;
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+// Events a subscription can receive
+pub const SUBSCRIBABLE_EVENTS: [&str; 7] = [
+    "guildEdited",
+    "memberUnbanned",
+    "messageCreated",
+    "messageDeleted",
+    "messageEmbedsUpdated",
+    "messageUpdated",
+    "messagesBulkDeleted",
+];
+// Attempts made for every delivery, waiting twice as long between each
+const MAX_ATTEMPTS: u32 = 5;
+const RETRY_DELAY: u64 = 1;
+// Seconds before a delivery attempt is aborted
+const DELIVERY_TIMEOUT: u64 = 10;
+// Failed deliveries in a row before a subscription is disabled
+const MAX_FAILURES: i32 = 10;
+// Seconds deliveries are kept for
+const DELIVERY_RETENTION: i64 = 7 * 24 * 60 * 60;
+// Seconds between purges of old deliveries
+const PURGE_INTERVAL: u64 = 60 * 60;
+
+pub struct DispatchJob {
+    guild_id: String,
+    event: String,
+    payload: String,
+}
+
+pub type Dispatcher = mpsc::UnboundedSender<DispatchJob>;
+
+// Queue a guild event for every subscription listening to it
+pub fn dispatch(dispatcher: &Dispatcher, guild_id: &str, message: SSEEvent<'_>) {
+    let payload = serde_json::to_value(VersionedEvent {
+        v: EVENT_VERSION,
+        event: &message,
+    })
+    .unwrap();
+
+    let _ = dispatcher.send(DispatchJob {
+        guild_id: guild_id.to_string(),
+        event: payload["event"].as_str().unwrap().to_string(),
+        payload: payload.to_string(),
+    });
+}
+
+// Sign the timestamp and payload with the subscription's secret
+pub fn sign(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// Start the delivery worker
+pub fn spawn(database: crate::Database) -> Dispatcher {
+    let (tx, mut rx) = mpsc::unbounded_channel::<DispatchJob>();
+
+    tokio::spawn(async move {
+        let client =
+            utils::network::public_client(DELIVERY_TIMEOUT, Policy::none(), "FlyWayWebhooks/1.0");
+
+        tokio::spawn(purge(database.clone()));
+
+        while let Some(job) = rx.recv().await {
+            let subscriptions = database
+                .query(
+                    "SELECT * FROM subscriptions WHERE guild = $1 AND enabled AND $2 = any(events)",
+                    &[&Uuid::parse_str(&job.guild_id).unwrap(), &job.event],
+                )
+                .await;
+
+            let subscriptions = match subscriptions {
+                Ok(subscriptions) => subscriptions,
+                Err(e) => {
+                    println!("{:}", e);
+                    continue;
+                }
+            };
+
+            let job = Arc::new(job);
+            for subscription in subscriptions {
+                tokio::spawn(deliver(
+                    database.clone(),
+                    client.clone(),
+                    job.clone(),
+                    subscription.get::<&str, Uuid>("id"),
+                    subscription.get::<&str, String>("url"),
+                    subscription.get::<&str, String>("secret"),
+                ));
+            }
+        }
+    });
+
+    tx
+}
+
+// Periodically purge the deliveries past their retention period
+async fn purge(database: crate::Database) {
+    loop {
+        let expiry = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64
+            - DELIVERY_RETENTION;
+
+        if let Err(e) = database
+            .execute(
+                "DELETE FROM subscription_deliveries WHERE creation < $1",
+                &[&expiry],
+            )
+            .await
+        {
+            println!("{:}", e);
+        }
+
+        sleep(Duration::from_secs(PURGE_INTERVAL)).await;
+    }
+}
+
+struct Attempt {
+    attempt: u32,
+    status: Option<i32>,
+    success: bool,
+    error: Option<String>,
+    timestamp: i64,
+}
+
+// Post the event until it is accepted, waiting twice as long between each attempt
+async fn attempt_delivery<F>(
+    client: &Client,
+    url: &str,
+    secret: &str,
+    job: &DispatchJob,
+    delivery_id: Uuid,
+    retry_delay: Duration,
+    log: impl Fn(Attempt) -> F,
+) -> bool
+where
+    F: Future<Output = ()>,
+{
+    for attempt in 1..=MAX_ATTEMPTS {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        let response = client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .header("X-FlyWay-Event", &job.event)
+            .header("X-FlyWay-Delivery", delivery_id.to_string())
+            .header("X-FlyWay-Timestamp", timestamp.to_string())
+            .header(
+                "X-FlyWay-Signature",
+                format!("sha256={}", sign(secret, timestamp, &job.payload)),
+            )
+            .body(job.payload.clone())
+            .send()
+            .await;
+
+        let (status, error) = match response {
+            Ok(response) => (Some(response.status().as_u16() as i32), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        let success = status.is_some_and(|status| (200..300).contains(&status));
+
+        log(Attempt {
+            attempt,
+            status,
+            success,
+            error,
+            timestamp,
+        })
+        .await;
+
+        if success {
+            return true;
+        }
+
+        if attempt < MAX_ATTEMPTS {
+            sleep(retry_delay * (1 << (attempt - 1))).await;
+        }
+    }
+
+    false
+}
+
+// Deliver the event, logging every attempt
+async fn deliver(
+    database: crate::Database,
+    client: Client,
+    job: Arc<DispatchJob>,
+    subscription_id: Uuid,
+    url: String,
+    secret: String,
+) {
+    let delivery_id = Uuid::new_v4();
+    let log = |attempt: Attempt| {
+        let database = database.clone();
+        let job = job.clone();
+
+        async move {
+            let _ = database
+            .execute(
+                "INSERT INTO subscription_deliveries (id, delivery, subscription, event, attempt, status, success, error, creation) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+                &[
+                    &Uuid::new_v4(),
+                    &delivery_id,
+                    &subscription_id,
+                    &job.event,
+                    &(attempt.attempt as i32),
+                    &attempt.status,
+                    &attempt.success,
+                    &attempt.error,
+                    &attempt.timestamp,
+                ],
+            )
+            .await;
+        }
+    };
+
+    // The URL is checked again, in case it was saved before being disallowed
+    let success = if utils::network::is_allowed_url(&url) {
+        attempt_delivery(
+            &client,
+            &url,
+            &secret,
+            &job,
+            delivery_id,
+            Duration::from_secs(RETRY_DELAY),
+            log,
+        )
+        .await
+    } else {
+        log(Attempt {
+            attempt: 1,
+            status: None,
+            success: false,
+            error: Some("URL is not allowed".to_string()),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64,
+        })
+        .await;
+
+        false
+    };
+
+    if success {
+        let _ = database
+            .execute(
+                "UPDATE subscriptions SET failures = 0 WHERE id = $1",
+                &[&subscription_id],
+            )
+            .await;
+
+        return;
+    }
+
+    // Disable the subscription after too many failed deliveries
+    let failures = database
+        .query_one(
+            "UPDATE subscriptions SET failures = failures + 1 WHERE id = $1 RETURNING failures",
+            &[&subscription_id],
+        )
+        .await;
+
+    if failures.is_ok_and(|failures| failures.get::<&str, i32>("failures") >= MAX_FAILURES) {
+        let _ = database
+            .execute(
+                "UPDATE subscriptions SET enabled = false WHERE id = $1",
+                &[&subscription_id],
+            )
+            .await;
+    }
+}
+
+// Check if every event can be subscribed to
+pub fn are_subscribable(events: &[String]) -> bool {
+    !events.is_empty()
+        && events
+            .iter()
+            .all(|event| SUBSCRIBABLE_EVENTS.contains(&event.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::utils::test_server;
+
+    use rocket::tokio::sync::Mutex;
+
+    // Start a local HTTP server answering with the statuses in order
+    async fn stand_in(statuses: Vec<u16>) -> (String, test_server::Requests) {
+        let (url, requests) =
+            test_server::start(statuses.into_iter().map(|status| (status, "")).collect()).await;
+
+        (format!("{}/hook", url), requests)
+    }
+
+    fn job() -> DispatchJob {
+        DispatchJob {
+            guild_id: Uuid::new_v4().to_string(),
+            event: "guildEdited".to_string(),
+            payload: r#"{"v":1,"event":"guildEdited"}"#.to_string(),
+        }
+    }
+
+    // Deliver to the stand-in, returning whether it succeeded and the statuses logged
+    async fn run(url: &str, retry_delay: Duration) -> (bool, Vec<Option<i32>>) {
+        let logged = Arc::new(Mutex::new(Vec::new()));
+        let success = attempt_delivery(
+            &Client::new(),
+            url,
+            "secret",
+            &job(),
+            Uuid::new_v4(),
+            retry_delay,
+            |attempt| {
+                let logged = logged.clone();
+                async move { logged.lock().await.push(attempt.status) }
+            },
+        )
+        .await;
+
+        let logged = logged.lock().await.clone();
+        (success, logged)
+    }
+
+    #[test]
+    fn signs_timestamp_and_payload() {
+        assert_eq!(
+            sign("secret", 1700000000, r#"{"v":1,"event":"guildEdited"}"#),
+            "debc44c92e4d77b6b3cb5e2d3808ad8794ccccc1fcb51779065563ece8e6205e"
+        );
+    }
+
+    #[rocket::async_test]
+    async fn delivers_signed_event() {
+        let (url, requests) = stand_in(vec![204]).await;
+        let (success, logged) = run(&url, Duration::from_millis(1)).await;
+
+        assert!(success);
+        assert_eq!(logged, vec![Some(204)]);
+
+        let requests = requests.lock().await;
+        let request = &requests[0];
+        let timestamp = request.headers["x-flyway-timestamp"]
+            .parse::<i64>()
+            .unwrap();
+
+        assert_eq!(request.headers["x-flyway-event"], "guildEdited");
+        assert_eq!(String::from_utf8_lossy(&request.body), job().payload);
+        assert_eq!(
+            request.headers["x-flyway-signature"],
+            format!("sha256={}", sign("secret", timestamp, &job().payload))
+        );
+    }
+
+    #[rocket::async_test]
+    async fn retries_with_backoff() {
+        let (url, requests) = stand_in(vec![500, 502, 200]).await;
+        let (success, logged) = run(&url, Duration::from_millis(50)).await;
+
+        assert!(success);
+        assert_eq!(logged, vec![Some(500), Some(502), Some(200)]);
+
+        // Waits twice as long before every retry
+        let requests = requests.lock().await;
+        assert!(requests[1].received - requests[0].received >= Duration::from_millis(50));
+        assert!(requests[2].received - requests[1].received >= Duration::from_millis(100));
+    }
+
+    #[rocket::async_test]
+    async fn gives_up_after_max_attempts() {
+        let (url, requests) = stand_in(vec![503]).await;
+        let (success, logged) = run(&url, Duration::from_millis(1)).await;
+
+        assert!(!success);
+        assert_eq!(logged, vec![Some(503); MAX_ATTEMPTS as usize]);
+        assert_eq!(requests.lock().await.len(), MAX_ATTEMPTS as usize);
+    }
+}
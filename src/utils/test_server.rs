@@ -0,0 +1,105 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use rocket::tokio::{
+    self,
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    sync::Mutex,
+    time::Instant,
+};
+use std::{collections::HashMap, sync::Arc};
+
+pub struct Request {
+    pub received: Instant,
+    pub line: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+pub type Requests = Arc<Mutex<Vec<Request>>>;
+
+// Start a local HTTP server answering with the responses in order, then repeating the last one
+pub async fn start(responses: Vec<(u16, &'static str)>) -> (String, Requests) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let received = requests.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut data = Vec::new();
+            let mut buffer = [0; 4096];
+
+            // Read the headers, then the body
+            let (head, length) = loop {
+                let read = stream.read(&mut buffer).await.unwrap();
+                data.extend_from_slice(&buffer[..read]);
+
+                if let Some(end) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+                    let head = String::from_utf8_lossy(&data[..end]).to_string();
+                    let length = head
+                        .lines()
+                        .find_map(|line| {
+                            line.to_ascii_lowercase()
+                                .strip_prefix("content-length: ")
+                                .map(|length| length.parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    data.drain(..end + 4);
+                    break (head, length);
+                }
+            };
+
+            while data.len() < length {
+                let read = stream.read(&mut buffer).await.unwrap();
+                data.extend_from_slice(&buffer[..read]);
+            }
+
+            let mut requests = received.lock().await;
+            let (status, body) = responses[requests.len().min(responses.len() - 1)];
+            requests.push(Request {
+                received: Instant::now(),
+                line: head.lines().next().unwrap_or_default().to_string(),
+                headers: head
+                    .lines()
+                    .skip(1)
+                    .filter_map(|line| line.split_once(": "))
+                    .map(|(name, value)| (name.to_ascii_lowercase(), value.to_string()))
+                    .collect(),
+                body: data,
+            });
+            drop(requests);
+
+            let _ = stream
+                .write_all(
+                    format!(
+                        "HTTP/1.1 {} Test\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    )
+                    .as_bytes(),
+                )
+                .await;
+        }
+    });
+
+    (url, requests)
+}
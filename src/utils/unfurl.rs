@@ -0,0 +1,463 @@
+/*
+Copyright (C) 2025  FlyWay Chat
+This file is part of FlyWay Chat.
+
+FlyWay Chat is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+FlyWay Chat is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with FlyWay Chat.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    routes::structs::{Embed, Message},
+    utils::{self, network::is_allowed, subscriptions::Dispatcher},
+};
+
+use reqwest::{header::CONTENT_TYPE, redirect::Policy, Client, Url};
+use rocket::{
+    serde::json::{to_value, Value},
+    tokio::{self, sync::mpsc},
+};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+// Maximum amount of links unfurled per message
+const MAX_LINKS: usize = 5;
+// Maximum amount of bytes read from a page
+const MAX_PAGE_SIZE: usize = 512 * 1024;
+// Seconds before a page fetch is aborted
+const FETCH_TIMEOUT: u64 = 5;
+// Maximum amount of redirects followed
+const MAX_REDIRECTS: usize = 3;
+// Seconds a cached preview stays valid
+const META_TTL: i64 = 24 * 60 * 60;
+// Maximum length of an embed's title and description
+const MAX_TITLE_LENGTH: usize = 256;
+const MAX_DESCRIPTION_LENGTH: usize = 1024;
+
+pub struct UnfurlJob {
+    guild_id: Option<String>,
+    channel_id: String,
+    message_id: String,
+    urls: Vec<Url>,
+    recipients: Vec<String>,
+}
+
+pub type Unfurler = mpsc::UnboundedSender<UnfurlJob>;
+
+// Extract the links of a message's content
+fn extract_urls(content: &str) -> Vec<Url> {
+    let mut urls: Vec<Url> = Vec::new();
+
+    for word in content.split_whitespace() {
+        if !word.starts_with("http://") && !word.starts_with("https://") {
+            continue;
+        }
+
+        let word = word.trim_end_matches(['.', ',', '!', '?', ')', '>', '"', '\'']);
+
+        if let Ok(url) = Url::parse(word) {
+            if is_allowed(&url) && !urls.contains(&url) {
+                urls.push(url);
+            }
+        }
+
+        if urls.len() >= MAX_LINKS {
+            break;
+        }
+    }
+
+    urls
+}
+
+// Queue a new message for unfurling
+pub fn queue(
+    unfurler: &Unfurler,
+    guild_id: Option<&str>,
+    channel_id: &str,
+    message: &Message,
+    recipients: Vec<String>,
+) {
+    let urls = extract_urls(&message.content);
+
+    if urls.is_empty() {
+        return;
+    }
+
+    let _ = unfurler.send(UnfurlJob {
+        guild_id: guild_id.map(|guild_id| guild_id.to_string()),
+        channel_id: channel_id.to_string(),
+        message_id: message.id.clone(),
+        urls,
+        recipients,
+    });
+}
+
+// Start the unfurling worker
+pub fn spawn(
+    database: crate::Database,
+    sse_clients: crate::SSEClients,
+    dispatcher: Dispatcher,
+) -> Unfurler {
+    let (tx, mut rx) = mpsc::unbounded_channel::<UnfurlJob>();
+
+    tokio::spawn(async move {
+        let client = utils::network::public_client(
+            FETCH_TIMEOUT,
+            Policy::custom(|attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    attempt.error("too many redirects")
+                } else if is_allowed(attempt.url()) {
+                    attempt.follow()
+                } else {
+                    attempt.stop()
+                }
+            }),
+            "FlyWayBot/1.0 (link preview)",
+        );
+
+        while let Some(job) = rx.recv().await {
+            let database = database.clone();
+            let client = client.clone();
+            let sse_clients = sse_clients.clone();
+            let dispatcher = dispatcher.clone();
+
+            tokio::spawn(async move {
+                let mut embeds = Vec::new();
+                for url in job.urls {
+                    if let Some(embed) = get_embed(&database, &client, &url).await {
+                        embeds.push(embed);
+                    }
+                }
+
+                if embeds.is_empty() {
+                    return;
+                }
+
+                let updated = database
+                    .execute(
+                        "UPDATE messages SET embeds = $1 WHERE id = $2",
+                        &[
+                            &embeds
+                                .iter()
+                                .map(|embed| to_value(embed).unwrap())
+                                .collect::<Vec<Value>>(),
+                            &Uuid::parse_str(&job.message_id).unwrap(),
+                        ],
+                    )
+                    .await;
+
+                if let Err(e) = updated {
+                    println!("{:}", e);
+                    return;
+                }
+
+                // Broadcast messageEmbedsUpdated event to every recipient
+                for recipient in job.recipients {
+                    utils::sse::broadcast(
+                        &sse_clients,
+                        &recipient,
+                        utils::structs::SSEEvent::MessageEmbedsUpdated {
+                            guild_id: job.guild_id.as_deref(),
+                            channel_id: &job.channel_id,
+                            message_id: &job.message_id,
+                            embeds: &embeds,
+                        },
+                    )
+                    .await;
+                }
+
+                if let Some(guild_id) = job.guild_id.as_deref() {
+                    utils::subscriptions::dispatch(
+                        &dispatcher,
+                        guild_id,
+                        utils::structs::SSEEvent::MessageEmbedsUpdated {
+                            guild_id: Some(guild_id),
+                            channel_id: &job.channel_id,
+                            message_id: &job.message_id,
+                            embeds: &embeds,
+                        },
+                    );
+                }
+            });
+        }
+    });
+
+    tx
+}
+
+// Get the embed of a link, from the cache or by fetching it
+async fn get_embed(database: &tokio_postgres::Client, client: &Client, url: &Url) -> Option<Embed> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+
+    // Check if the link is cached
+    let cached = database
+        .query_one(
+            "SELECT * FROM meta WHERE url = $1 AND creation > $2",
+            &[&url.as_str(), &(now - META_TTL)],
+        )
+        .await;
+
+    let embed = match cached {
+        Ok(meta) => Embed {
+            url: url.to_string(),
+            title: meta.get::<&str, Option<String>>("title"),
+            description: meta.get::<&str, Option<String>>("description"),
+            image: meta.get::<&str, Option<String>>("image"),
+        },
+        Err(_) => {
+            let embed = fetch_embed(client, url).await.unwrap_or(Embed {
+                url: url.to_string(),
+                title: None,
+                description: None,
+                image: None,
+            });
+
+            // Failed fetches are cached too
+            let _ = database
+                .execute(
+                    "INSERT INTO meta (url, creation, title, description, image) VALUES ($1, $2, $3, $4, $5)
+                    ON CONFLICT (url) DO UPDATE SET creation = $2, title = $3, description = $4, image = $5",
+                    &[
+                        &url.as_str(),
+                        &now,
+                        &embed.title,
+                        &embed.description,
+                        &embed.image,
+                    ],
+                )
+                .await;
+
+            embed
+        }
+    };
+
+    if embed.title.is_none() && embed.description.is_none() && embed.image.is_none() {
+        return None;
+    }
+
+    Some(embed)
+}
+
+// Fetch the page and read its OpenGraph and Twitter card tags
+async fn fetch_embed(client: &Client, url: &Url) -> Option<Embed> {
+    let mut response = client.get(url.clone()).send().await.ok()?;
+
+    // Check if the page is HTML
+    if !response.status().is_success()
+        || !response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_some_and(|content_type| content_type.starts_with("text/html"))
+    {
+        return None;
+    }
+
+    let final_url = response.url().clone();
+
+    // Read at most MAX_PAGE_SIZE bytes
+    let mut data = Vec::new();
+    while let Ok(Some(chunk)) = response.chunk().await {
+        data.extend_from_slice(&chunk);
+
+        if data.len() >= MAX_PAGE_SIZE {
+            data.truncate(MAX_PAGE_SIZE);
+            break;
+        }
+    }
+
+    let html = String::from_utf8_lossy(&data);
+    let tags = parse_meta_tags(&html);
+    let get = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| tags.get(*key))
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    Some(Embed {
+        url: url.to_string(),
+        title: get(&["og:title", "twitter:title"])
+            .or_else(|| parse_title(&html))
+            .map(|title| truncate(title, MAX_TITLE_LENGTH)),
+        description: get(&["og:description", "twitter:description", "description"])
+            .map(|description| truncate(description, MAX_DESCRIPTION_LENGTH)),
+        image: get(&["og:image", "og:image:url", "twitter:image"])
+            .and_then(|image| final_url.join(&image).ok())
+            .filter(|image| ["http", "https"].contains(&image.scheme()))
+            .map(|image| image.to_string()),
+    })
+}
+
+fn truncate(text: String, length: usize) -> String {
+    text.chars().take(length).collect()
+}
+
+// Get the content of every <meta> tag, keyed by its property or name
+fn parse_meta_tags(html: &str) -> HashMap<String, String> {
+    let lower = html.to_ascii_lowercase();
+    let mut tags = HashMap::new();
+
+    for (start, _) in lower.match_indices("<meta") {
+        let end = match lower[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+
+        let attributes = parse_attributes(&html[start + 5..end]);
+        let key = attributes
+            .get("property")
+            .or_else(|| attributes.get("name"));
+
+        if let (Some(key), Some(content)) = (key, attributes.get("content")) {
+            tags.entry(key.to_ascii_lowercase())
+                .or_insert_with(|| content.to_string());
+        }
+    }
+
+    tags
+}
+
+// Get the content of the <title> tag
+fn parse_title(html: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let start = lower.find("<title")?;
+    let start = start + lower[start..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+
+    Some(decode_entities(html[start..end].trim())).filter(|title| !title.is_empty())
+}
+
+fn parse_attributes(tag: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    let mut rest = tag;
+
+    loop {
+        rest = rest.trim_start_matches(|char: char| char.is_whitespace() || char == '/');
+
+        let name_end = rest
+            .find(|char: char| char == '=' || char == '/' || char.is_whitespace())
+            .unwrap_or(rest.len());
+
+        if name_end == 0 {
+            break;
+        }
+
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+
+        let Some(after) = rest.strip_prefix('=') else {
+            attributes.insert(name, String::new());
+            continue;
+        };
+
+        let after = after.trim_start();
+        let (value, remaining) = match after.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let inner = &after[1..];
+                match inner.find(quote) {
+                    Some(end) => (&inner[..end], &inner[end + 1..]),
+                    None => (inner, ""),
+                }
+            }
+            _ => {
+                let end = after
+                    .find(|char: char| char.is_whitespace())
+                    .unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            }
+        };
+
+        attributes.insert(name, decode_entities(value));
+        rest = remaining;
+    }
+
+    attributes
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_public_links() {
+        let urls = extract_urls(
+            "see https://example.com/a, http://example.org/b) and https://example.com/a! \
+             ftp://example.net http://127.0.0.1/admin http://[::1]/ https://example.com:8443/",
+        );
+
+        assert_eq!(
+            urls.iter().map(Url::as_str).collect::<Vec<_>>(),
+            ["https://example.com/a", "http://example.org/b"]
+        );
+    }
+
+    #[test]
+    fn extracts_limited_links() {
+        let content = (0..MAX_LINKS + 2)
+            .map(|index| format!("https://example.com/{index}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        assert_eq!(extract_urls(&content).len(), MAX_LINKS);
+    }
+
+    #[test]
+    fn parses_attributes() {
+        let attributes = parse_attributes(
+            r#" property="og:title" content='Crabs &amp; &quot;Rust&quot;' data-x=plain async /"#,
+        );
+
+        assert_eq!(attributes["property"], "og:title");
+        assert_eq!(attributes["content"], "Crabs & \"Rust\"");
+        assert_eq!(attributes["data-x"], "plain");
+        assert_eq!(attributes["async"], "");
+
+        let attributes = parse_attributes(r#" NAME = "description" content="unterminated"#);
+
+        assert_eq!(attributes["name"], "description");
+        assert_eq!(attributes["content"], "unterminated");
+    }
+
+    #[test]
+    fn parses_meta_tags_and_title() {
+        let html = r#"<html><head>
+            <TITLE> Crabs &lt;3 </TITLE>
+            <meta property="og:title" content="First">
+            <meta property="OG:TITLE" content="Second">
+            <meta name="description" content="About crabs"/>
+            </head></html>"#;
+
+        let tags = parse_meta_tags(html);
+
+        assert_eq!(tags["og:title"], "First");
+        assert_eq!(tags["description"], "About crabs");
+        assert_eq!(parse_title(html).as_deref(), Some("Crabs <3"));
+        assert_eq!(parse_title("<title> </title>"), None);
+    }
+}